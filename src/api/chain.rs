@@ -1,6 +1,7 @@
-use super::JsonApi;
+use super::{JsonApi, PubsubApi, NotificationStream};
 use crate::error::Result;
 use crate::helper;
+use crate::transports::SubscriptionId;
 use crate::types::{Bytes, TipSet, DomainSeparationTag, ChainEpoch, Randomness, BytesRef, TipSetKey,
                    BlockHeader, CidJsonRef, BlockMessages, MessageReceipt, ParentMessage, ObjStat,
                    BigIntWrapper, UnsignedMessage, HeadChange, BigInt, Cid};
@@ -8,9 +9,12 @@ use crate::types::{Bytes, TipSet, DomainSeparationTag, ChainEpoch, Randomness, B
 #[async_trait::async_trait]
 pub trait ChainApi: JsonApi {
 
-//    async fn chain_notify(&self) -> Result<(SubscriptionId, NotificationStream<Vec<HeadChange>>)> {
-//        self.subscribe("ChainNotify", vec![]).await
-//    }
+    async fn chain_notify(&self) -> Result<(SubscriptionId, NotificationStream<Vec<HeadChange>>)>
+        where
+            Self: PubsubApi,
+    {
+        self.subscribe("ChainNotify", vec![]).await
+    }
 
     async fn chain_head(&self) -> Result<TipSet> {
         self.request("ChainHead", vec![]).await
@@ -55,6 +59,26 @@ pub trait ChainApi: JsonApi {
             .await
     }
 
+    /// `chain_get_block_messages` for several blocks in a single batch request.
+    async fn chain_get_block_messages_batch(&self, cids: &[Cid]) -> Result<Vec<Result<BlockMessages>>> {
+        self.request_batch(
+            cids.iter()
+                .map(|cid| ("ChainGetBlockMessages", vec![helper::serialize(&CidJsonRef(cid))]))
+                .collect(),
+        )
+            .await
+    }
+
+    /// `chain_get_parent_receipts` for several blocks in a single batch request.
+    async fn chain_get_parent_receipts_batch(&self, cids: &[Cid]) -> Result<Vec<Result<Vec<MessageReceipt>>>> {
+        self.request_batch(
+            cids.iter()
+                .map(|cid| ("ChainGetParentReceipts", vec![helper::serialize(&CidJsonRef(cid))]))
+                .collect(),
+        )
+            .await
+    }
+
     async fn chain_get_parent_messages(&self, cid: &Cid) -> Result<Vec<ParentMessage>> {
         self.request("ChainGetParentMessages", vec![helper::serialize(&CidJsonRef(cid))])
             .await
@@ -86,7 +110,7 @@ pub trait ChainApi: JsonApi {
 
     async fn chain_stat_obj(&self, obj: &Cid, base: &Cid) -> Result<ObjStat> {
         self.request(
-            "ChainHasObj",
+            "ChainStatObj",
             vec![helper::serialize(&CidJsonRef(obj)), helper::serialize(&CidJsonRef(base))],
         )
             .await
@@ -121,11 +145,14 @@ pub trait ChainApi: JsonApi {
             .await
     }
 
-//    async fn chain_export(
-//        &self,
-//        key: &TipsetKey,
-//    ) -> Result<(SubscriptionId, NotificationStream<Bytes>)> {
-//        self.subscribe("ChainExport", vec![helper::serialize(key)])
-//            .await
-//    }
+    async fn chain_export(
+        &self,
+        key: &TipSetKey,
+    ) -> Result<(SubscriptionId, NotificationStream<Bytes>)>
+        where
+            Self: PubsubApi,
+    {
+        self.subscribe("ChainExport", vec![helper::serialize(key)])
+            .await
+    }
 }
\ No newline at end of file
@@ -44,7 +44,7 @@ pub trait ClientApi: JsonApi {
 
     async fn client_retrieve(&self, order: &RetrievalOrder, r#ref: &FileRef) -> Result<()> {
         self.request(
-            "ClientFindData",
+            "ClientRetrieve",
             vec![helper::serialize(order), helper::serialize(r#ref)],
         )
             .await
@@ -77,7 +77,7 @@ pub trait ClientApi: JsonApi {
 
     async fn client_gen_car(&self, r#ref: &FileRef, outpath: &str) -> Result<()> {
         self.request(
-            "ClientCalcCommP",
+            "ClientGenCar",
             vec![helper::serialize(r#ref), helper::serialize(&outpath)],
         )
             .await
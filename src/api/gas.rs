@@ -0,0 +1,71 @@
+use super::JsonApi;
+use crate::error::Result;
+use crate::helper;
+use crate::types::{TipSetKey, UnsignedMessage, MessageSendSpec, Address, TokenAmount};
+
+#[async_trait::async_trait]
+pub trait GasApi: JsonApi {
+    // estimates the fee cap for the message to be included within `max_queue_blocks` blocks.
+    async fn gas_estimate_fee_cap(
+        &self,
+        msg: &UnsignedMessage,
+        max_queue_blocks: i64,
+        key: &TipSetKey,
+    ) -> Result<TokenAmount> {
+        self.request(
+            "GasEstimateFeeCap",
+            vec![
+                helper::serialize(msg),
+                helper::serialize(&max_queue_blocks),
+                helper::serialize(key),
+            ],
+        )
+            .await
+    }
+
+    // estimates the premium for a message of `gas_limit` to be included within `nblocksincl` blocks.
+    async fn gas_estimate_gas_premium(
+        &self,
+        nblocksincl: u64,
+        sender: &Address,
+        gas_limit: i64,
+        key: &TipSetKey,
+    ) -> Result<TokenAmount> {
+        self.request(
+            "GasEstimateGasPremium",
+            vec![
+                helper::serialize(&nblocksincl),
+                helper::serialize(sender),
+                helper::serialize(&gas_limit),
+                helper::serialize(key),
+            ],
+        )
+            .await
+    }
+
+    async fn gas_estimate_gas_limit(&self, msg: &UnsignedMessage, key: &TipSetKey) -> Result<i64> {
+        self.request(
+            "GasEstimateGasLimit",
+            vec![helper::serialize(msg), helper::serialize(key)],
+        )
+            .await
+    }
+
+    // fills the gas fields of the message which are zero, the fee being capped by the spec.
+    async fn gas_estimate_message_gas(
+        &self,
+        msg: &UnsignedMessage,
+        spec: Option<&MessageSendSpec>,
+        key: &TipSetKey,
+    ) -> Result<UnsignedMessage> {
+        self.request(
+            "GasEstimateMessageGas",
+            vec![
+                helper::serialize(msg),
+                helper::serialize(&spec),
+                helper::serialize(key),
+            ],
+        )
+            .await
+    }
+}
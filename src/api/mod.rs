@@ -5,6 +5,9 @@ mod sync;
 mod mpool;
 mod miner;
 mod client;
+mod pubsub;
+mod state;
+mod gas;
 
 pub use common::CommpnApi;
 pub use chain::ChainApi;
@@ -13,11 +16,14 @@ pub use sync::SyncApi;
 pub use mpool::MpoolApi;
 pub use miner::MinerApi;
 pub use client::ClientApi;
+pub use pubsub::{PubsubApi, NotificationStream};
+pub use state::StateApi;
+pub use gas::GasApi;
 
-use crate::transports::{Http, Transport};
-use crate::error::Result;
+use crate::transports::{Transport, DuplexTransport, SubscriptionId, RequestId, execute_single, execute_batch};
+use crate::error::{Error, Result};
 use serde_json::Value;
-use jsonrpc_core::Params;
+use jsonrpc_core::{Call, MethodCall, Params, Request};
 
 #[async_trait::async_trait]
 pub trait JsonApi {
@@ -26,27 +32,97 @@ pub trait JsonApi {
         where
             M: AsRef<str> + Send,
             T: serde::de::DeserializeOwned;
+
+    /// Send Rpc requests in a single batch, the results are returned in order.
+    async fn request_batch<M, T>(&self, requests: Vec<(M, Vec<Value>)>) -> Result<Vec<Result<T>>>
+        where
+            M: AsRef<str> + Send,
+            T: serde::de::DeserializeOwned;
 }
 
 #[async_trait::async_trait]
-impl JsonApi for Http {
+impl<R: Transport + Send + Sync> JsonApi for R {
     async fn request<M, T>(&self, method: M, params: Vec<Value>) -> Result<T>
         where
             M: AsRef<str> + Send,
             T: serde::de::DeserializeOwned,
     {
-        Ok(self
-            .send(
-                format!("Filecoin.{}", method.as_ref()),
-                Params::Array(params),
-            )
-            .await?)
+        let (id, call) = self.prepare(format!("Filecoin.{}", method.as_ref()), Params::Array(params));
+        let request = Request::Single(call);
+        execute_single(self, id, &request).await.map_err(|e| with_request(e, request))
+    }
+
+    async fn request_batch<M, T>(&self, requests: Vec<(M, Vec<Value>)>) -> Result<Vec<Result<T>>>
+        where
+            M: AsRef<str> + Send,
+            T: serde::de::DeserializeOwned,
+    {
+        let (ids, calls): (Vec<RequestId>, Vec<Call>) = requests
+            .into_iter()
+            .map(|(method, params)| self.prepare(format!("Filecoin.{}", method.as_ref()), Params::Array(params)))
+            .unzip();
+        let request = Request::Batch(calls);
+        let results = execute_batch(self, &ids, &request).await?;
+        let calls = match request {
+            Request::Batch(calls) => calls,
+            Request::Single(_) => unreachable!(),
+        };
+        Ok(results
+            .into_iter()
+            .zip(calls)
+            .map(|(result, call)| {
+                result
+                    .and_then(|result| Ok(serde_json::from_value(result)?))
+                    .map_err(|e| with_call(e, call))
+            })
+            .collect())
     }
 }
 
-impl CommpnApi for Http {}
-impl ChainApi for Http {}
-impl WalletApi for Http {}
-impl SyncApi for Http {}
-impl MpoolApi for Http {}
-impl MinerApi for Http {}
+#[async_trait::async_trait]
+impl<R: DuplexTransport + Send + Sync> PubsubApi for R {
+    async fn subscribe<M, T>(&self, method: M, params: Vec<Value>) -> Result<(SubscriptionId, NotificationStream<T>)>
+        where
+            M: AsRef<str> + Send,
+            T: serde::de::DeserializeOwned,
+    {
+        let (id, call) = self.prepare(format!("Filecoin.{}", method.as_ref()), Params::Array(params));
+        let request = Request::Single(call);
+        let (subscription_id, receiver) = DuplexTransport::subscribe(self, id, &request)
+            .await
+            .map_err(|e| with_request(e, request))?;
+        Ok((subscription_id, NotificationStream::new(receiver)))
+    }
+
+    async fn unsubscribe(&self, id: SubscriptionId) -> Result<()> {
+        DuplexTransport::unsubscribe(self, id).await
+    }
+}
+
+// The params are only kept by the prepared call, and taken back on failure.
+fn with_call(e: Error, call: Call) -> Error {
+    match call {
+        Call::MethodCall(MethodCall { method, params: Params::Array(params), .. }) => e.with_call(method, params),
+        _ => e,
+    }
+}
+
+fn with_request(e: Error, request: Request) -> Error {
+    match request {
+        Request::Single(call) => with_call(e, call),
+        Request::Batch(_) => e,
+    }
+}
+
+impl<R: Transport + Send + Sync> CommpnApi for R {}
+impl<R: Transport + Send + Sync> ChainApi for R {}
+impl<R: Transport + Send + Sync> WalletApi for R {}
+impl<R: Transport + Send + Sync> SyncApi for R {}
+impl<R: Transport + Send + Sync> MpoolApi for R {}
+impl<R: Transport + Send + Sync> MinerApi for R {}
+impl<R: Transport + Send + Sync> StateApi for R {}
+impl<R: Transport + Send + Sync> ClientApi for R {}
+impl<R: Transport + Send + Sync> GasApi for R {}
+
+#[cfg(test)]
+mod tests;
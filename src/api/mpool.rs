@@ -1,8 +1,9 @@
-use super::JsonApi;
+use super::{JsonApi, PubsubApi, NotificationStream};
 use crate::error::Result;
 use crate::helper;
-use crate::types::{TipSetKey, SignedMessage, Cid, UnsignedMessage, Address, MpoolUpdate, BigInt,
-BigIntWrapper, CidJson};
+use crate::transports::SubscriptionId;
+use crate::types::{TipSetKey, SignedMessage, Cid, UnsignedMessage, Address, MpoolUpdate, TokenAmount,
+CidJson};
 
 #[async_trait::async_trait]
 pub trait MpoolApi: JsonApi {
@@ -28,28 +29,30 @@ pub trait MpoolApi: JsonApi {
             .await
     }
 
-//    async fn mpool_sub(&self) -> Result<(SubscriptionId, NotificationStream<MpoolUpdate>)> {
-//        self.subscribe("MpoolSub", vec![]).await
-//    }
+    async fn mpool_sub(&self) -> Result<(SubscriptionId, NotificationStream<MpoolUpdate>)>
+        where
+            Self: PubsubApi,
+    {
+        self.subscribe("MpoolSub", vec![]).await
+    }
 
+    // the single gas price of nodes older than Lotus v0.5, see `GasApi` for the current nodes.
     async fn mpool_estimate_gas_price(
         &self,
         nblocksincl: u64,
         addr: &Address,
         gas_limit: i64,
         key: &TipSetKey,
-    ) -> Result<BigInt> {
-        let price: BigIntWrapper = self
-            .request(
-                "MpoolEstimateGasPrice",
-                vec![
-                    helper::serialize(&nblocksincl),
-                    helper::serialize(addr),
-                    helper::serialize(&gas_limit),
-                    helper::serialize(key),
-                ],
-            )
-            .await?;
-        Ok(price.into_inner())
+    ) -> Result<TokenAmount> {
+        self.request(
+            "MpoolEstimateGasPrice",
+            vec![
+                helper::serialize(&nblocksincl),
+                helper::serialize(addr),
+                helper::serialize(&gas_limit),
+                helper::serialize(key),
+            ],
+        )
+            .await
     }
 }
\ No newline at end of file
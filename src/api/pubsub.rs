@@ -0,0 +1,48 @@
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use futures::Stream;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use super::JsonApi;
+use crate::error::Result;
+use crate::transports::{SubscriptionId, NotificationReceiver};
+
+#[async_trait::async_trait]
+pub trait PubsubApi: JsonApi {
+    /// Send Rpc request whose result is a notification channel.
+    async fn subscribe<M, T>(&self, method: M, params: Vec<Value>) -> Result<(SubscriptionId, NotificationStream<T>)>
+        where
+            M: AsRef<str> + Send,
+            T: DeserializeOwned;
+
+    /// Close the notification channel of the subscription.
+    async fn unsubscribe(&self, id: SubscriptionId) -> Result<()>;
+}
+
+/// Stream of the typed notifications of one subscription.
+///
+/// The stream ends when the node closes the channel or the connection is lost.
+pub struct NotificationStream<T> {
+    receiver: NotificationReceiver,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> NotificationStream<T> {
+    pub fn new(receiver: NotificationReceiver) -> Self {
+        Self {
+            receiver,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Stream for NotificationStream<T> {
+    type Item = Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver)
+            .poll_next(cx)
+            .map(|value| value.map(|value| Ok(serde_json::from_value(value)?)))
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use super::JsonApi;
+use crate::error::Result;
+use crate::helper;
+use crate::types::{Address, TipSetKey, Actor, MinerPower, MinerInfo, ChainSectorInfo, MarketBalance, MarketDeal,
+                   UnsignedMessage, InvocResult, MsgLookup, Cid, CidJsonRef};
+
+/// The State methods are used to query, inspect, and interact with chain state.
+/// An empty `TipSetKey` selects the current head.
+#[async_trait::async_trait]
+pub trait StateApi: JsonApi {
+    async fn state_get_actor(&self, actor: &Address, key: &TipSetKey) -> Result<Actor> {
+        self.request(
+            "StateGetActor",
+            vec![helper::serialize(actor), helper::serialize(key)],
+        )
+            .await
+    }
+
+    // returns the ID address of the given address.
+    async fn state_lookup_id(&self, addr: &Address, key: &TipSetKey) -> Result<Address> {
+        self.request(
+            "StateLookupID",
+            vec![helper::serialize(addr), helper::serialize(key)],
+        )
+            .await
+    }
+
+    // returns the public key address of the given ID address.
+    async fn state_account_key(&self, addr: &Address, key: &TipSetKey) -> Result<Address> {
+        self.request(
+            "StateAccountKey",
+            vec![helper::serialize(addr), helper::serialize(key)],
+        )
+            .await
+    }
+
+    async fn state_miner_power(&self, miner: &Address, key: &TipSetKey) -> Result<MinerPower> {
+        self.request(
+            "StateMinerPower",
+            vec![helper::serialize(miner), helper::serialize(key)],
+        )
+            .await
+    }
+
+    async fn state_miner_info(&self, miner: &Address, key: &TipSetKey) -> Result<MinerInfo> {
+        self.request(
+            "StateMinerInfo",
+            vec![helper::serialize(miner), helper::serialize(key)],
+        )
+            .await
+    }
+
+    // returns all sectors of the miner, without filtering.
+    async fn state_miner_sectors(&self, miner: &Address, key: &TipSetKey) -> Result<Vec<ChainSectorInfo>> {
+        self.request(
+            "StateMinerSectors",
+            vec![
+                helper::serialize(miner),
+                serde_json::Value::Null,
+                helper::serialize(&false),
+                helper::serialize(key),
+            ],
+        )
+            .await
+    }
+
+    async fn state_list_miners(&self, key: &TipSetKey) -> Result<Vec<Address>> {
+        self.request("StateListMiners", vec![helper::serialize(key)])
+            .await
+    }
+
+    async fn state_list_actors(&self, key: &TipSetKey) -> Result<Vec<Address>> {
+        self.request("StateListActors", vec![helper::serialize(key)])
+            .await
+    }
+
+    async fn state_market_balance(&self, addr: &Address, key: &TipSetKey) -> Result<MarketBalance> {
+        self.request(
+            "StateMarketBalance",
+            vec![helper::serialize(addr), helper::serialize(key)],
+        )
+            .await
+    }
+
+    // returns the deals of the storage market, keyed by deal id.
+    async fn state_market_deals(&self, key: &TipSetKey) -> Result<HashMap<String, MarketDeal>> {
+        self.request("StateMarketDeals", vec![helper::serialize(key)])
+            .await
+    }
+
+    async fn state_network_name(&self) -> Result<String> {
+        self.request("StateNetworkName", vec![]).await
+    }
+
+    // runs the message on top of the given tipset without putting it on chain.
+    async fn state_call(&self, msg: &UnsignedMessage, key: &TipSetKey) -> Result<InvocResult> {
+        self.request(
+            "StateCall",
+            vec![helper::serialize(msg), helper::serialize(key)],
+        )
+            .await
+    }
+
+    // replays the message which was included in the given tipset.
+    async fn state_replay(&self, key: &TipSetKey, cid: &Cid) -> Result<InvocResult> {
+        self.request(
+            "StateReplay",
+            vec![helper::serialize(key), helper::serialize(&CidJsonRef(cid))],
+        )
+            .await
+    }
+
+    // waits until the message is executed and has `confidence` tipsets on top of it.
+    async fn state_wait_msg(&self, cid: &Cid, confidence: u64) -> Result<MsgLookup> {
+        self.request(
+            "StateWaitMsg",
+            vec![helper::serialize(&CidJsonRef(cid)), helper::serialize(&confidence)],
+        )
+            .await
+    }
+
+    // returns none if the message hasn't been executed yet.
+    async fn state_search_msg(&self, cid: &Cid) -> Result<Option<MsgLookup>> {
+        self.request("StateSearchMsg", vec![helper::serialize(&CidJsonRef(cid))])
+            .await
+    }
+}
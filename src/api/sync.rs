@@ -1,7 +1,8 @@
-use super::JsonApi;
+use super::{JsonApi, PubsubApi, NotificationStream};
 use crate::error::Result;
 use crate::helper;
-use crate::types::{SyncState, Cid, CidJsonRef, BlockMsg};
+use crate::transports::SubscriptionId;
+use crate::types::{SyncState, Cid, CidJsonRef, BlockMsg, BlockHeader};
 
 #[async_trait::async_trait]
 pub trait SyncApi: JsonApi {
@@ -14,11 +15,14 @@ pub trait SyncApi: JsonApi {
             .await
     }
 
-//    async fn sync_incoming_blocks(
-//        &self,
-//    ) -> Result<(SubscriptionId, NotificationStream<BlockHeader>)> {
-//        self.subscribe("SyncIncomingBlocks", vec![]).await
-//    }
+    async fn sync_incoming_blocks(
+        &self,
+    ) -> Result<(SubscriptionId, NotificationStream<BlockHeader>)>
+        where
+            Self: PubsubApi,
+    {
+        self.subscribe("SyncIncomingBlocks", vec![]).await
+    }
 
     async fn sync_mark_bad(&self, bad_cid: &Cid) -> Result<()> {
         self.request("SyncMarkBad", vec![helper::serialize(&CidJsonRef(bad_cid))])
@@ -0,0 +1,193 @@
+//! Conformance of the API traits with the Lotus JSON-RPC methods: every method is called
+//! against the mock server, then the name and the JSON shape of the params it received
+//! are checked against the table below.
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use tokio::runtime::Runtime;
+use super::*;
+use crate::Http;
+use crate::testing::MockServer;
+use crate::types::*;
+
+fn shape(params: &[Value]) -> Vec<&'static str> {
+    params
+        .iter()
+        .map(|param| match param {
+            Value::Null => "null",
+            Value::Bool(_) => "bool",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        })
+        .collect()
+}
+
+fn sample<T: DeserializeOwned>(value: Value) -> T {
+    serde_json::from_value(value).unwrap()
+}
+
+macro_rules! conformance {
+    ($server:expr; $($call:expr => $method:literal [$($shape:literal),*];)*) => {
+        $(
+            // No method has a handler, only the request matters.
+            let _ = $call.await;
+            let calls = $server.take_calls();
+            assert_eq!(calls.len(), 1, "{}", stringify!($call));
+            assert_eq!(calls[0].method, concat!("Filecoin.", $method), "{}", stringify!($call));
+            assert_eq!(shape(&calls[0].params), vec![$($shape),*] as Vec<&str>, "{}", stringify!($call));
+        )*
+    };
+}
+
+#[test]
+fn rpc_methods() {
+    let cid_json = json!({"/": "bafy2bzacecgw6dqj4bctnbnyqfujltkwu7xc7ttaaato4i5miroxr4bayhfea"});
+    let cid = sample::<CidJson>(cid_json.clone()).0;
+    let key: TipSetKey = sample(json!([{"/": "bafy2bzacebwut2il7udv5d3yzscpwbomvj5ocq6lkxh4kcusiy5juesvpun4c"}]));
+    let addr = Address::new_id_addr(1000).unwrap();
+    let peer_id: PeerId = "QmYyQSo1c1Ym7orWxLYvCrM2EmxFTANf8wXmmE7DWjhx5N".parse().unwrap();
+    let peer_addr_info: PeerAddrInfo = sample(json!({"ID": peer_id.to_string(), "Addrs": []}));
+    let msg: UnsignedMessage = sample(json!({
+        "Version": 0, "To": "t01", "From": "t01000", "Nonce": 0, "Value": "0",
+        "GasLimit": 1000, "GasFeeCap": "0", "GasPremium": "0", "Method": 0, "Params": "",
+    }));
+    let signature = Signature::new_bls(vec![0; 96]);
+    let signed_msg = SignedMessage { message: msg.clone(), signature: signature.clone() };
+    let header: BlockHeader = sample(json!({
+        "Miner": "t01000", "Ticket": {"VRFProof": ""}, "ElectionProof": {"VRFProof": ""},
+        "BeaconEntries": null, "WinPoStProof": [], "Parents": key, "ParentWeight": "0", "Height": 1,
+        "ParentStateRoot": cid_json, "ParentMessageReceipts": cid_json, "Messages": cid_json,
+        "BLSAggregate": signature, "Timestamp": 0, "BlockSig": signature, "ForkSignaling": 0,
+    }));
+    let block_msg = BlockMsg { header, bls_messages: vec![], secpk_messages: vec![] };
+    let template: BlockTemplate = sample(json!({
+        "Miner": "t01000", "Parents": key, "Ticket": {"VRFProof": ""}, "Eproof": {"VRFProof": ""},
+        "BeaconValues": [], "Messages": [], "Epoch": 1, "Timestamp": 0, "WinningPoStProof": [],
+    }));
+    let key_info = KeyInfo { r#type: keystore::KeyType::Secp256k1, private_key: Bytes::from(vec![1; 32]) };
+    let file_ref = FileRef { path: "/tmp/file".into(), is_car: false };
+    let deal_params = StartDealParams {
+        wallet: addr.clone(),
+        miner: addr.clone(),
+        epoch_price: TokenAmount::from_atto(1),
+        min_blocks_duration: 100,
+        deal_start_epoch: 10,
+    };
+    let order: RetrievalOrder = sample(json!({
+        "Root": cid_json, "Size": 1, "Total": "1", "PaymentInterval": 1, "PaymentIntervalIncrease": 1,
+        "Client": "t01000", "Miner": "t01000", "MinerPeerID": peer_id.to_string(),
+    }));
+
+    let mut rt = Runtime::new().unwrap();
+    rt.block_on(async {
+        let server = MockServer::start();
+        let api = Http::new(&server.url());
+
+        conformance! { server;
+            // CommpnApi
+            api.auth_verify("token") => "AuthVerify" ["string"];
+            api.auth_new(&[Permission::Read]) => "AuthNew" ["array"];
+            api.net_connectedness(&peer_id) => "NetConnectedness" ["string"];
+            api.net_peers() => "NetPeers" [];
+            api.net_connect(&peer_addr_info) => "NetConnect" ["object"];
+            api.net_addrs_listen() => "NetAddrsListen" [];
+            api.net_disconnect(&peer_id) => "NetDisconnect" ["string"];
+            api.net_find_peer(&peer_id) => "NetFindPeer" ["string"];
+            api.id() => "ID" [];
+            api.version() => "Version" [];
+            api.log_list() => "LogList" [];
+            api.log_set_level("rpc", "debug") => "LogSetLevel" ["string", "string"];
+            api.shutdown() => "Shutdown" [];
+
+            // ChainApi
+            api.chain_head() => "ChainHead" [];
+            api.chain_get_randomness(&key, &DomainSeparationTag::TicketProduction, 1, &[1])
+                => "ChainGetRandomness" ["array", "number", "number", "string"];
+            api.chain_get_block(&cid) => "ChainGetBlock" ["object"];
+            api.chain_get_tipset(&key) => "ChainGetTipSet" ["array"];
+            api.chain_get_block_messages(&cid) => "ChainGetBlockMessages" ["object"];
+            api.chain_get_parent_receipts(&cid) => "ChainGetParentReceipts" ["object"];
+            api.chain_get_parent_messages(&cid) => "ChainGetParentMessages" ["object"];
+            api.chain_get_tipset_by_height(1, &key) => "ChainGetTipSetByHeight" ["number", "array"];
+            api.chain_read_obj(&cid) => "ChainReadObj" ["object"];
+            api.chain_has_obj(&cid) => "ChainHasObj" ["object"];
+            api.chain_stat_obj(&cid, &cid) => "ChainStatObj" ["object", "object"];
+            api.chain_set_head(&key) => "ChainSetHead" ["array"];
+            api.chain_get_genesis() => "ChainGetGenesis" [];
+            api.chain_tipset_weight(&key) => "ChainTipSetWeight" ["array"];
+            api.chain_get_message(&cid) => "ChainGetMessage" ["object"];
+            api.chain_get_path(&key, &key) => "ChainGetPath" ["array", "array"];
+
+            // WalletApi
+            api.wallet_new(SignatureType::Bls) => "WalletNew" ["number"];
+            api.wallet_has(&addr) => "WalletHas" ["string"];
+            api.wallet_list() => "WalletList" [];
+            api.wallet_balance(&addr) => "WalletBalance" ["string"];
+            api.wallet_sign(&addr, &[1]) => "WalletSign" ["string", "string"];
+            api.wallet_sign_message(&addr, &msg) => "WalletSignMessage" ["string", "object"];
+            api.wallet_verify(&addr, &[1], &signature) => "WalletVerify" ["string", "string", "object"];
+            api.wallet_default_address() => "WalletDefaultAddress" [];
+            api.wallet_set_default(&addr) => "WalletSetDefault" ["string"];
+            api.wallet_export(&addr) => "WalletExport" ["string"];
+            api.wallet_import(&key_info) => "WalletImport" ["object"];
+
+            // SyncApi
+            api.sync_state() => "SyncState" [];
+            api.sync_submit_block(&block_msg) => "SyncSubmitBlock" ["object"];
+            api.sync_mark_bad(&cid) => "SyncMarkBad" ["object"];
+            api.sync_check_bad(&cid) => "SyncCheckBad" ["object"];
+
+            // MpoolApi
+            api.mpool_pending(&key) => "MpoolPending" ["array"];
+            api.mpool_push(&signed_msg) => "MpoolPush" ["object"];
+            api.mpool_push_message(&msg) => "MpoolPushMessage" ["object"];
+            api.mpool_get_nonce(&addr) => "MpoolGetNonce" ["string"];
+            api.mpool_estimate_gas_price(1, &addr, 1000, &key)
+                => "MpoolEstimateGasPrice" ["number", "string", "number", "array"];
+
+            // MinerApi
+            api.miner_get_base_info(&addr, 1, &key) => "MinerGetBaseInfo" ["string", "number", "array"];
+            api.miner_create_block(&template) => "MinerCreateBlock" ["object"];
+
+            // ClientApi
+            api.client_import(&file_ref) => "ClientImport" ["object"];
+            api.client_start_deal(&deal_params) => "ClientStartDeal" ["object"];
+            api.client_get_deal_info(&cid) => "ClientGetDealInfo" ["object"];
+            api.client_list_deals() => "ClientListDeals" [];
+            api.client_has_local(&cid) => "ClientHasLocal" ["object"];
+            api.client_find_data(&cid) => "ClientFindData" ["object"];
+            api.client_retrieve(&order, &file_ref) => "ClientRetrieve" ["object", "object"];
+            api.client_calc_comm_p("/tmp/file", &addr) => "ClientCalcCommP" ["string", "string"];
+            api.client_gen_car(&file_ref, "/tmp/file.car") => "ClientGenCar" ["object", "string"];
+            api.client_list_imports() => "ClientListImports" [];
+
+            // StateApi
+            api.state_get_actor(&addr, &key) => "StateGetActor" ["string", "array"];
+            api.state_lookup_id(&addr, &key) => "StateLookupID" ["string", "array"];
+            api.state_account_key(&addr, &key) => "StateAccountKey" ["string", "array"];
+            api.state_miner_power(&addr, &key) => "StateMinerPower" ["string", "array"];
+            api.state_miner_info(&addr, &key) => "StateMinerInfo" ["string", "array"];
+            api.state_miner_sectors(&addr, &key) => "StateMinerSectors" ["string", "null", "bool", "array"];
+            api.state_list_miners(&key) => "StateListMiners" ["array"];
+            api.state_list_actors(&key) => "StateListActors" ["array"];
+            api.state_market_balance(&addr, &key) => "StateMarketBalance" ["string", "array"];
+            api.state_market_deals(&key) => "StateMarketDeals" ["array"];
+            api.state_network_name() => "StateNetworkName" [];
+            api.state_call(&msg, &key) => "StateCall" ["object", "array"];
+            api.state_replay(&key, &cid) => "StateReplay" ["array", "object"];
+            api.state_wait_msg(&cid, 5) => "StateWaitMsg" ["object", "number"];
+            api.state_search_msg(&cid) => "StateSearchMsg" ["object"];
+
+            // GasApi
+            api.gas_estimate_fee_cap(&msg, 20, &key) => "GasEstimateFeeCap" ["object", "number", "array"];
+            api.gas_estimate_gas_premium(10, &addr, 1000, &key)
+                => "GasEstimateGasPremium" ["number", "string", "number", "array"];
+            api.gas_estimate_gas_limit(&msg, &key) => "GasEstimateGasLimit" ["object", "array"];
+            api.gas_estimate_message_gas(&msg, None, &key) => "GasEstimateMessageGas" ["object", "null", "array"];
+            api.gas_estimate_message_gas(&msg, Some(&MessageSendSpec::default()), &key)
+                => "GasEstimateMessageGas" ["object", "object", "array"];
+        }
+    });
+}
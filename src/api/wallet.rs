@@ -1,7 +1,7 @@
 use super::JsonApi;
 use crate::error::Result;
 use crate::helper;
-use crate::types::{Address, SignatureType, BigInt, BigIntWrapper, Signature, BytesRef, UnsignedMessage,
+use crate::types::{Address, SignatureType, TokenAmount, Signature, BytesRef, UnsignedMessage,
                    SignedMessage, KeyInfo};
 
 #[async_trait::async_trait]
@@ -20,11 +20,9 @@ pub trait WalletApi: JsonApi {
         self.request("WalletList", vec![]).await
     }
 
-    async fn wallet_balance(&self, addr: &Address) -> Result<BigInt> {
-        let bigint: BigIntWrapper = self
-            .request("WalletBalance", vec![helper::serialize(addr)])
-            .await?;
-        Ok(bigint.into_inner())
+    async fn wallet_balance(&self, addr: &Address) -> Result<TokenAmount> {
+        self.request("WalletBalance", vec![helper::serialize(addr)])
+            .await
     }
 
     async fn wallet_sign(&self, addr: &Address, msg: &[u8]) -> Result<Signature> {
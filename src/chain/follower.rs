@@ -0,0 +1,290 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use futures::{Stream, StreamExt};
+use log::debug;
+use crate::api::ChainApi;
+use crate::error::Result;
+use crate::types::{ChainEpoch, HeadChange, HeadChangeType, TipSet};
+
+/// Callbacks of a `ChainFollower`, called in the order of the chain.
+#[async_trait::async_trait]
+pub trait ChainHandler: Send {
+    /// `tipset` is on the chain, on top of the last tipset applied.
+    async fn apply(&mut self, tipset: &TipSet) -> Result<()>;
+
+    /// `tipset`, the last tipset applied, was reorganized out of the chain.
+    async fn revert(&mut self, tipset: &TipSet) -> Result<()>;
+}
+
+/// Configuration of the `ChainFollower`.
+#[derive(Clone, Debug)]
+pub struct FollowerConfig {
+    /// Epochs a tipset stays below the head before it's applied. The tipsets of a
+    /// reorganization shorter than this are never applied, so never reverted either.
+    pub finality: ChainEpoch,
+    /// Delay between two polls of the head of the chain.
+    pub poll_interval: Duration,
+}
+
+impl Default for FollowerConfig {
+    fn default() -> Self {
+        Self {
+            finality: 0,
+            poll_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Keeps a local view of the head of the chain, and calls a `ChainHandler` with the tipsets
+/// applied and reverted when the head changes, as given by `chain_get_path`.
+///
+/// The first head seen starts the follower: the tipset `finality` epochs below it is the
+/// first tipset applied. A callback which fails is called again, with the same tipset, at
+/// the next update.
+#[derive(Debug, Default)]
+pub struct ChainFollower {
+    config: FollowerConfig,
+    head: Option<TipSet>,
+    /// Tipsets to revert, from the highest one.
+    reverts: VecDeque<TipSet>,
+    /// Tipsets to apply after the reverts, from the lowest one.
+    applies: VecDeque<TipSet>,
+}
+
+impl ChainFollower {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_config(config: FollowerConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// The latest head seen, which may not be applied yet.
+    pub fn head(&self) -> Option<&TipSet> {
+        self.head.as_ref()
+    }
+
+    /// Poll the head of the chain every `poll_interval`, forever.
+    ///
+    /// Only returns the errors of the node and of the handler.
+    pub async fn poll<A, H>(&mut self, api: &A, handler: &mut H) -> Result<()>
+        where
+            A: ChainApi + Sync,
+            H: ChainHandler,
+    {
+        loop {
+            let head = api.chain_head().await?;
+            self.update(api, handler, head).await?;
+            tokio::time::delay_for(self.config.poll_interval).await;
+        }
+    }
+
+    /// Follow the head changes of `changes`, from `chain_notify`, until the stream ends.
+    ///
+    /// The follower can follow a new stream after a reconnection: the changes missed
+    /// meanwhile are caught up with `chain_get_path`.
+    pub async fn follow<A, H, S>(&mut self, api: &A, handler: &mut H, mut changes: S) -> Result<()>
+        where
+            A: ChainApi + Sync,
+            H: ChainHandler,
+            S: Stream<Item = Result<Vec<HeadChange>>> + Unpin,
+    {
+        while let Some(changes) = changes.next().await {
+            // The path to the new head is computed again, notifications may be coalesced.
+            let head = changes?
+                .into_iter()
+                .rev()
+                .find(|change| !matches!(change.r#type, HeadChangeType::Revert));
+            if let Some(head) = head {
+                self.update(api, handler, head.val).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the local view to `head`, then call the handler with the tipsets to revert, and
+    /// with the tipsets to apply which are at least `finality` epochs below `head`.
+    pub async fn update<A, H>(&mut self, api: &A, handler: &mut H, head: TipSet) -> Result<()>
+        where
+            A: ChainApi + Sync,
+            H: ChainHandler,
+    {
+        let current = match &self.head {
+            Some(current) => current.key(),
+            None => {
+                let start = if self.config.finality > 0 && head.height > self.config.finality {
+                    api.chain_get_tipset_by_height(head.height - self.config.finality, &head.key())
+                        .await?
+                } else {
+                    head.clone()
+                };
+                let key = start.key();
+                self.applies.push_back(start.clone());
+                self.head = Some(start);
+                key
+            }
+        };
+
+        if self.head.as_ref().map(|current| &current.cids) != Some(&head.cids) {
+            let path = api.chain_get_path(&current, &head.key()).await?;
+            debug!("Head changed to {}, {} changes", head.height, path.len());
+            for change in path {
+                match change.r#type {
+                    HeadChangeType::Revert => self.revert(change.val),
+                    HeadChangeType::Apply | HeadChangeType::Current => self.applies.push_back(change.val),
+                }
+            }
+            self.head = Some(head);
+        }
+        self.deliver(handler).await
+    }
+
+    // A tipset which wasn't applied yet is just dropped.
+    fn revert(&mut self, tipset: TipSet) {
+        match self.applies.back() {
+            Some(last) if last.cids == tipset.cids => {
+                self.applies.pop_back();
+            }
+            _ => self.reverts.push_back(tipset),
+        }
+    }
+
+    async fn deliver<H: ChainHandler>(&mut self, handler: &mut H) -> Result<()> {
+        while let Some(tipset) = self.reverts.front() {
+            handler.revert(tipset).await?;
+            self.reverts.pop_front();
+        }
+        let height = self.head.as_ref().map_or(0, |head| head.height);
+        while let Some(tipset) = self.applies.front() {
+            if height - tipset.height < self.config.finality {
+                break;
+            }
+            handler.apply(tipset).await?;
+            self.applies.pop_front();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cid::Cid;
+    use tokio::runtime::Runtime;
+    use crate::Http;
+    use crate::error::Error;
+    use crate::testing::{MockChain, MockServer};
+
+    fn cid(name: &str) -> Cid {
+        MockChain::cid(name)
+    }
+
+    // A chain of tipsets named by `(name, height, parent)`.
+    fn chain(server: &MockServer, tipsets: &[(&str, ChainEpoch, Option<&str>)]) -> MockChain {
+        let chain = MockChain::new();
+        for (name, height, parent) in tipsets {
+            chain.add(name, *height, *parent);
+        }
+        server.chain(&chain);
+        chain
+    }
+
+    // Records the callbacks, failing the first call for `fail`.
+    #[derive(Default)]
+    struct Recorder {
+        calls: Vec<(&'static str, ChainEpoch, Cid)>,
+        fail: Option<Cid>,
+    }
+
+    impl Recorder {
+        fn record(&mut self, callback: &'static str, tipset: &TipSet) -> Result<()> {
+            if self.fail.as_ref() == Some(&tipset.cids[0]) {
+                self.fail = None;
+                return Err(Error::Transport("database is locked".into()));
+            }
+            self.calls.push((callback, tipset.height, tipset.cids[0].clone()));
+            Ok(())
+        }
+
+        fn take(&mut self) -> Vec<(&'static str, ChainEpoch, Cid)> {
+            std::mem::take(&mut self.calls)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl ChainHandler for Recorder {
+        async fn apply(&mut self, tipset: &TipSet) -> Result<()> {
+            self.record("apply", tipset)
+        }
+
+        async fn revert(&mut self, tipset: &TipSet) -> Result<()> {
+            self.record("revert", tipset)
+        }
+    }
+
+    const CHAIN: &[(&str, ChainEpoch, Option<&str>)] = &[
+        ("g", 0, None),
+        ("a1", 1, Some("g")),
+        ("a2", 2, Some("a1")),
+        ("a3", 3, Some("a2")),
+        ("b2", 2, Some("a1")),
+        ("b3", 3, Some("b2")),
+        ("b4", 4, Some("b3")),
+    ];
+
+    #[test]
+    fn reorganization() {
+        let mut rt = Runtime::new().unwrap();
+        let server = rt.enter(MockServer::start);
+        let chain = chain(&server, CHAIN);
+        let http = Http::new(&server.url());
+        let mut follower = ChainFollower::with_config(FollowerConfig { finality: 1, ..FollowerConfig::default() });
+        let mut recorder = Recorder::default();
+
+        rt.block_on(follower.update(&http, &mut recorder, chain.get("a3"))).unwrap();
+        assert_eq!(recorder.take(), vec![("apply", 2, cid("a2"))]);
+
+        // a3 was never applied, b4 isn't final yet.
+        recorder.fail = Some(cid("b3"));
+        let updated = rt.block_on(follower.update(&http, &mut recorder, chain.get("b4")));
+        assert!(updated.is_err());
+        assert_eq!(recorder.take(), vec![("revert", 2, cid("a2")), ("apply", 2, cid("b2"))]);
+        assert_eq!(follower.head().unwrap().cids, vec![cid("b4")]);
+
+        // The failed callback is called again.
+        rt.block_on(follower.update(&http, &mut recorder, chain.get("b4"))).unwrap();
+        assert_eq!(recorder.take(), vec![("apply", 3, cid("b3"))]);
+    }
+
+    #[test]
+    fn follow_notifications() {
+        let mut rt = Runtime::new().unwrap();
+        let server = rt.enter(MockServer::start);
+        let chain = chain(&server, CHAIN);
+        let http = Http::new(&server.url());
+        let mut follower = ChainFollower::new();
+        let mut recorder = Recorder::default();
+
+        let change = |r#type, name| HeadChange { r#type, val: chain.get(name) };
+        let changes = futures::stream::iter(vec![
+            Ok(vec![change(HeadChangeType::Current, "a2")]),
+            Ok(vec![change(HeadChangeType::Apply, "a3")]),
+            // Coalesced, the apply of b3 is missing.
+            Ok(vec![change(HeadChangeType::Revert, "a3"), change(HeadChangeType::Apply, "b4")]),
+        ]);
+        rt.block_on(follower.follow(&http, &mut recorder, changes)).unwrap();
+        assert_eq!(recorder.take(), vec![
+            ("apply", 2, cid("a2")),
+            ("apply", 3, cid("a3")),
+            ("revert", 3, cid("a3")),
+            ("revert", 2, cid("a2")),
+            ("apply", 2, cid("b2")),
+            ("apply", 3, cid("b3")),
+            ("apply", 4, cid("b4")),
+        ]);
+    }
+}
@@ -0,0 +1,7 @@
+//! Following the chain: delivering the tipsets applied to and reverted from the head of the
+//! chain, in order, to an indexer, and walking the tipsets of the past.
+mod follower;
+mod walker;
+
+pub use follower::{ChainFollower, ChainHandler, FollowerConfig};
+pub use walker::{TipSetMessages, TipSetWalker};
@@ -0,0 +1,321 @@
+use futures::future;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use crate::api::ChainApi;
+use crate::error::{Error, Result};
+use crate::types::{ChainEpoch, MessageReceipt, ParentMessage, TipSet, TipSetKey};
+
+#[derive(Clone, Copy, Debug)]
+enum Direction {
+    Backward,
+    Forward,
+}
+
+/// A tipset with the messages of its parent tipset, whose execution gave its state, and
+/// their receipts in the same order.
+#[derive(Clone, Debug)]
+pub struct TipSetMessages {
+    pub tipset: TipSet,
+    pub messages: Vec<ParentMessage>,
+    pub receipts: Vec<MessageReceipt>,
+}
+
+/// Walks the tipsets of the chain over a range of heights, as a stream. The null rounds
+/// are skipped.
+///
+/// Backwards, each tipset is fetched from the parents of the previous one. Forwards, up
+/// to `concurrency` tipsets are fetched ahead with `chain_get_tipset_by_height`, on the
+/// chain of the head when the walk starts. The height of a null round costs the fetch of
+/// the tipset below it, which is dropped.
+///
+/// ```ignore
+/// let mut tipsets = TipSetWalker::backward().from_height(100_000).walk(&http);
+/// while let Some(tipset) = tipsets.next().await {
+///     index(tipset?)?;
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct TipSetWalker {
+    direction: Direction,
+    from_height: ChainEpoch,
+    to_height: Option<ChainEpoch>,
+    concurrency: usize,
+    checkpoint: Option<TipSet>,
+}
+
+// The next tipset of a backward walk.
+enum Next {
+    Tipset(TipSet),
+    Parents(TipSetKey),
+    Done,
+}
+
+impl TipSetWalker {
+    /// Walk from the head down to genesis.
+    pub fn backward() -> Self {
+        Self::new(Direction::Backward)
+    }
+
+    /// Walk from genesis up to the head.
+    pub fn forward() -> Self {
+        Self::new(Direction::Forward)
+    }
+
+    fn new(direction: Direction) -> Self {
+        Self {
+            direction,
+            from_height: 0,
+            to_height: None,
+            concurrency: 8,
+            checkpoint: None,
+        }
+    }
+
+    /// The lowest height of the walk, genesis by default.
+    pub fn from_height(mut self, height: ChainEpoch) -> Self {
+        self.from_height = height;
+        self
+    }
+
+    /// The highest height of the walk, the head by default.
+    pub fn to_height(mut self, height: ChainEpoch) -> Self {
+        self.to_height = Some(height);
+        self
+    }
+
+    /// Fetch up to `concurrency` tipsets ahead when walking forwards, and the messages of
+    /// as many tipsets, 8 by default.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Resume the walk after `checkpoint`, the last tipset of a previous walk. Backwards,
+    /// the walk goes on with the parents of `checkpoint`. Forwards, the walk fails with
+    /// `Error::Reorganized` if `checkpoint` isn't on the chain of the head anymore.
+    pub fn resume(mut self, checkpoint: TipSet) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// The stream of the tipsets, in the order of the walk.
+    pub fn walk<'a, A>(self, api: &'a A) -> BoxStream<'a, Result<TipSet>>
+        where
+            A: ChainApi + Sync,
+    {
+        match self.direction {
+            Direction::Backward => self.walk_backward(api),
+            Direction::Forward => self.walk_forward(api),
+        }
+    }
+
+    /// The stream of the tipsets with the messages of their parent and the receipts.
+    pub fn walk_with_messages<'a, A>(self, api: &'a A) -> BoxStream<'a, Result<TipSetMessages>>
+        where
+            A: ChainApi + Sync,
+    {
+        let concurrency = self.concurrency;
+        self.walk(api)
+            .map(move |tipset| async move {
+                let tipset = tipset?;
+                // The blocks of a tipset share their parent messages.
+                let block = &tipset.cids[0];
+                let (messages, receipts) = futures::try_join!(
+                    api.chain_get_parent_messages(block),
+                    api.chain_get_parent_receipts(block),
+                )?;
+                Ok(TipSetMessages { tipset, messages, receipts })
+            })
+            .buffered(concurrency)
+            .boxed()
+    }
+
+    fn walk_backward<'a, A>(self, api: &'a A) -> BoxStream<'a, Result<TipSet>>
+        where
+            A: ChainApi + Sync,
+    {
+        let from_height = self.from_height;
+        stream::once(async move { self.backward_start(api).await })
+            .map_ok(move |first| stream::try_unfold(first, move |next| next.tipset(api, from_height)))
+            .try_flatten()
+            .boxed()
+    }
+
+    fn walk_forward<'a, A>(self, api: &'a A) -> BoxStream<'a, Result<TipSet>>
+        where
+            A: ChainApi + Sync,
+    {
+        let concurrency = self.concurrency;
+        stream::once(async move { self.forward_heights(api).await })
+            .map(move |heights| match heights {
+                Ok((anchor, heights)) => stream::iter(heights)
+                    .map(move |height| {
+                        let anchor = anchor.clone();
+                        async move { (height, api.chain_get_tipset_by_height(height, &anchor).await) }
+                    })
+                    .buffered(concurrency)
+                    // The tipset of a null round is the tipset below it.
+                    .filter_map(|(height, tipset)| future::ready(match tipset {
+                        Ok(tipset) if tipset.height != height => None,
+                        tipset => Some(tipset),
+                    }))
+                    .boxed(),
+                Err(e) => stream::once(future::ready(Err(e))).boxed(),
+            })
+            .flatten()
+            .boxed()
+    }
+
+    // The highest tipset of a backward walk.
+    async fn backward_start<A>(&self, api: &A) -> Result<Next>
+        where
+            A: ChainApi + Sync,
+    {
+        let mut tipset = match &self.checkpoint {
+            Some(checkpoint) if checkpoint.height <= self.from_height => return Ok(Next::Done),
+            Some(checkpoint) => api.chain_get_tipset(&checkpoint.parents()).await?,
+            None => api.chain_head().await?,
+        };
+        if let Some(to_height) = self.to_height.filter(|to_height| *to_height < tipset.height) {
+            tipset = api.chain_get_tipset_by_height(to_height, &tipset.key()).await?;
+        }
+        Ok(Next::Tipset(tipset))
+    }
+
+    // The key of the head whose chain is walked, and the heights to walk.
+    async fn forward_heights<A>(&self, api: &A) -> Result<(TipSetKey, std::ops::RangeInclusive<ChainEpoch>)>
+        where
+            A: ChainApi + Sync,
+    {
+        let head = api.chain_head().await?;
+        let from_height = match &self.checkpoint {
+            Some(checkpoint) => {
+                let tipset = api.chain_get_tipset_by_height(checkpoint.height, &head.key()).await?;
+                if tipset.cids != checkpoint.cids {
+                    return Err(Error::Reorganized(format!(
+                        "checkpoint {} at {} is not on the chain of the head {}",
+                        checkpoint.key(),
+                        checkpoint.height,
+                        head.key(),
+                    )));
+                }
+                self.from_height.max(checkpoint.height + 1)
+            }
+            None => self.from_height,
+        };
+        let to_height = self.to_height.map_or(head.height, |height| height.min(head.height));
+        Ok((head.key(), from_height..=to_height))
+    }
+}
+
+impl Next {
+    // The tipset, if not below `from_height`, and the next one.
+    async fn tipset<A>(self, api: &A, from_height: ChainEpoch) -> Result<Option<(TipSet, Next)>>
+        where
+            A: ChainApi + Sync,
+    {
+        let tipset = match self {
+            Next::Tipset(tipset) => tipset,
+            Next::Parents(parents) => api.chain_get_tipset(&parents).await?,
+            Next::Done => return Ok(None),
+        };
+        if tipset.height < from_height {
+            return Ok(None);
+        }
+        let next = if tipset.height > from_height { Next::Parents(tipset.parents()) } else { Next::Done };
+        Ok(Some((tipset, next)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cid::Cid;
+    use futures::TryStreamExt;
+    use serde_json::json;
+    use tokio::runtime::Runtime;
+    use crate::Http;
+    use crate::testing::{MockChain, MockServer};
+    use crate::types::CidJson;
+
+    fn cid(height: ChainEpoch) -> Cid {
+        MockChain::cid(&height.to_string())
+    }
+
+    // A chain of 11 epochs with null rounds at 4 and 7, named by height.
+    fn chain(server: &MockServer) -> MockChain {
+        let chain = MockChain::new();
+        let mut parent = None;
+        for height in (0..=10).filter(|height| *height != 4 && *height != 7) {
+            chain.add(&height.to_string(), height, parent.as_deref());
+            parent = Some(height.to_string());
+        }
+        server.chain(&chain);
+        chain
+    }
+
+    fn heights(tipsets: Vec<TipSet>) -> Vec<ChainEpoch> {
+        tipsets.into_iter().map(|tipset| tipset.height).collect()
+    }
+
+    #[test]
+    fn walk_backward() {
+        let mut rt = Runtime::new().unwrap();
+        let server = rt.enter(MockServer::start);
+        let chain = chain(&server);
+        let http = Http::new(&server.url());
+
+        let walker = TipSetWalker::backward().from_height(2).concurrency(3);
+        let walked = rt.block_on(walker.clone().walk(&http).try_collect::<Vec<_>>()).unwrap();
+        assert_eq!(heights(walked), vec![10, 9, 8, 6, 5, 3, 2]);
+        // Through the parents, without fetching the null rounds.
+        let methods = server.take_calls().into_iter().map(|call| call.method).collect::<Vec<_>>();
+        assert_eq!(methods[0], "Filecoin.ChainHead");
+        assert_eq!(methods[1..], vec!["Filecoin.ChainGetTipSet"; 6][..]);
+
+        let walked = rt.block_on(walker.clone().to_height(7).walk(&http).try_collect::<Vec<_>>()).unwrap();
+        assert_eq!(heights(walked), vec![6, 5, 3, 2]);
+
+        // The parent of the checkpoint is below a null round.
+        let walker = walker.resume(chain.get("5"));
+        let walked = rt.block_on(walker.walk(&http).try_collect::<Vec<_>>()).unwrap();
+        assert_eq!(heights(walked), vec![3, 2]);
+    }
+
+    #[test]
+    fn walk_forward() {
+        let mut rt = Runtime::new().unwrap();
+        let server = rt.enter(MockServer::start);
+        let chain = chain(&server);
+        server
+            .fixture("ChainGetParentMessages", json!([{
+                "Cid": {"/": "bafy2bzacebwut2il7udv5d3yzscpwbomvj5ocq6lkxh4kcusiy5juesvpun4c"},
+                "Message": {
+                    "Version": 0, "To": "t01", "From": "t01000", "Nonce": 5, "Value": "1",
+                    "GasLimit": 1000, "GasFeeCap": "1", "GasPremium": "1", "Method": 0, "Params": "",
+                },
+            }]))
+            .fixture("ChainGetParentReceipts", json!([{"ExitCode": 0, "Return": "", "GasUsed": "500"}]));
+        let http = Http::new(&server.url());
+
+        let walker = TipSetWalker::forward().to_height(8).resume(chain.get("5"));
+        let walked = rt.block_on(walker.walk(&http).try_collect::<Vec<_>>()).unwrap();
+        assert_eq!(heights(walked), vec![6, 8]);
+
+        // The checkpoint was on a fork.
+        let fork = chain.add("5b", 5, Some("3"));
+        let walked = rt.block_on(TipSetWalker::forward().resume(fork).walk(&http).try_collect::<Vec<_>>());
+        assert!(matches!(walked, Err(Error::Reorganized(_))));
+
+        let walker = TipSetWalker::forward().from_height(9);
+        let walked = rt.block_on(walker.walk_with_messages(&http).try_collect::<Vec<_>>()).unwrap();
+        assert_eq!(walked.iter().map(|walked| walked.tipset.height).collect::<Vec<_>>(), vec![9, 10]);
+        assert_eq!((walked[0].messages.len(), walked[0].receipts.len()), (1, 1));
+        let blocks = server
+            .calls()
+            .into_iter()
+            .filter(|call| call.method == "Filecoin.ChainGetParentMessages")
+            .map(|call| call.params[0].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(blocks, vec![json!(CidJson(cid(9))), json!(CidJson(cid(10)))]);
+    }
+}
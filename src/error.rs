@@ -3,7 +3,10 @@ use jsonrpc_core::Error as RPCError;
 use derive_more::{Display, From};
 use serde_json::Error as SerdeError;
 use reqwest::Error as ReqwestError;
+use tokio_tungstenite::tungstenite::Error as WsError;
 use std::io::Error as IoError;
+use serde_json::Value;
+use crate::types::crypto::CryptoError;
 use serde::export::From;
 
 /// Lotus `Result` type.
@@ -23,9 +26,38 @@ pub enum Error {
     #[display(fmt = "Transport error: {}", _0)]
     #[from(ignore)]
     Transport(String),
+    /// the response breaks the JSON-RPC protocol, or the call can't be answered locally
+    #[display(fmt = "Protocol error: {}", _0)]
+    #[from(ignore)]
+    Protocol(String),
     /// rpc error
     #[display(fmt = "RPC error: {:?}", _0)]
+    #[from(ignore)]
     Rpc(RPCError),
+    /// the actor doesn't exist in the state
+    #[display(fmt = "Actor not found: {}", _0)]
+    #[from(ignore)]
+    ActorNotFound(String),
+    /// the nonce of the message is lower than the next nonce of the sender
+    #[display(fmt = "Nonce too low: {}", _0)]
+    #[from(ignore)]
+    NonceTooLow(String),
+    /// the balance of the sender doesn't cover the message
+    #[display(fmt = "Insufficient funds: {}", _0)]
+    #[from(ignore)]
+    InsufficientFunds(String),
+    /// the token is missing or lacks the permission of the method
+    #[display(fmt = "Permission denied: {}", _0)]
+    #[from(ignore)]
+    PermissionDenied(String),
+    /// the tipset isn't known by the node
+    #[display(fmt = "TipSet not found: {}", _0)]
+    #[from(ignore)]
+    TipSetNotFound(String),
+    /// the tipset was reorganized out of the chain of the head
+    #[display(fmt = "Reorganized out of the chain: {}", _0)]
+    #[from(ignore)]
+    Reorganized(String),
     /// io error
     #[display(fmt = "IO error: {}", _0)]
     Io(IoError),
@@ -35,22 +67,108 @@ pub enum Error {
     /// Lotus internal error
     #[display(fmt = "Internal lotus error")]
     Internal,
+    /// the message fails when estimating its gas
+    #[display(fmt = "Gas estimation failed: {}", _0)]
+    #[from(ignore)]
+    Estimation(String),
+    /// invalid client configuration
+    #[display(fmt = "Config error: {}", _0)]
+    #[from(ignore)]
+    Config(String),
+    /// error of a call, with the method and params of the call
+    #[display(fmt = "{} failed: {}", method, error)]
+    #[from(ignore)]
+    Call {
+        method: String,
+        params: Vec<Value>,
+        error: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Attach the method and params of the failed call, e.g. `Filecoin.ChainHead`.
+    pub fn with_call<M: Into<String>>(self, method: M, params: Vec<Value>) -> Self {
+        match self {
+            Error::Call { .. } => self,
+            error => Error::Call {
+                method: method.into(),
+                params,
+                error: Box::new(error),
+            },
+        }
+    }
+
+    /// The method of the failed call, if known.
+    pub fn method(&self) -> Option<&str> {
+        match self {
+            Error::Call { method, .. } => Some(method),
+            _ => None,
+        }
+    }
+
+    /// The error without the call context.
+    pub fn kind(&self) -> &Error {
+        match self {
+            Error::Call { error, .. } => error.kind(),
+            error => error,
+        }
+    }
+
+    /// Whether the same call may succeed later, i.e. the node couldn't be reached,
+    /// as opposed to the calls the node rejects and the protocol errors.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self.kind(), Error::Unreachable | Error::Transport(_) | Error::Io(_))
+    }
+}
+
+// Lotus fails to load the tipset of a call, given by its key, with
+// `loading tipset {bafy..}: get block bafy..: blockstore: block not found`, or just the
+// `get block` part for `ChainGetTipSet`.
+fn is_tipset_not_found(message: &str) -> bool {
+    (message.starts_with("loading tipset") || message.starts_with("get block "))
+        && (message.contains("blockstore: block not found") || message.contains("ipld: could not find"))
+}
+
+// Lotus returns its errors as `ServerError(1)` with the chain of error messages,
+// e.g. `resolution lookup failed (t01234): resolve address t01234: actor not found`.
+impl From<RPCError> for Error {
+    fn from(e: RPCError) -> Self {
+        let message = e.message.to_lowercase();
+        if message.contains("actor not found") {
+            Error::ActorNotFound(e.message)
+        } else if message.contains("nonce too low") {
+            Error::NonceTooLow(e.message)
+        } else if message.contains("not enough funds") || message.contains("insufficient funds") {
+            Error::InsufficientFunds(e.message)
+        } else if message.contains("missing permission") || message.contains("permission denied") {
+            Error::PermissionDenied(e.message)
+        } else if is_tipset_not_found(&message) {
+            Error::TipSetNotFound(e.message)
+        } else if message.contains("estimating gas used") {
+            Error::Estimation(e.message)
+        } else {
+            Error::Rpc(e)
+        }
+    }
 }
 
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         use self::Error::*;
         match *self {
-            Unreachable | Signing(_) | Transport(_) | Json(_) | Internal => None,
+            Unreachable | Signing(_) | Transport(_) | Json(_) | Internal | Config(_) | Estimation(_) => None,
+            ActorNotFound(_) | NonceTooLow(_) | InsufficientFunds(_) | PermissionDenied(_) | TipSetNotFound(_) => None,
+            Reorganized(_) | Protocol(_) => None,
             Rpc(ref e) => Some(e),
             Io(ref e) => Some(e),
+            Call { ref error, .. } => Some(error.as_ref()),
         }
     }
 }
 
 impl From<SerdeError> for Error {
     fn from(e: SerdeError) -> Self {
-        Error::Json(format!("{:?}", e))
+        Error::Json(e.to_string())
     }
 }
 
@@ -60,6 +178,18 @@ impl From<ReqwestError> for Error {
     }
 }
 
+impl From<CryptoError> for Error {
+    fn from(e: CryptoError) -> Self {
+        Error::Signing(e.to_string())
+    }
+}
+
+impl From<WsError> for Error {
+    fn from(e: WsError) -> Self {
+        Error::Transport(format!("{:?}", e))
+    }
+}
+
 impl Clone for Error {
     fn clone(&self) -> Self {
         use self::Error::*;
@@ -67,10 +197,24 @@ impl Clone for Error {
             Unreachable => Unreachable,
             Json(e) => Json(e.clone()),
             Transport(s) => Transport(s.clone()),
+            Protocol(s) => Protocol(s.clone()),
             Rpc(e) => Rpc(e.clone()),
             Io(e) => Io(IoError::from(e.kind())),
             Signing(e) => Signing(e.clone()),
             Internal => Internal,
+            Config(e) => Config(e.clone()),
+            Estimation(e) => Estimation(e.clone()),
+            ActorNotFound(e) => ActorNotFound(e.clone()),
+            NonceTooLow(e) => NonceTooLow(e.clone()),
+            InsufficientFunds(e) => InsufficientFunds(e.clone()),
+            PermissionDenied(e) => PermissionDenied(e.clone()),
+            TipSetNotFound(e) => TipSetNotFound(e.clone()),
+            Reorganized(e) => Reorganized(e.clone()),
+            Call { method, params, error } => Call {
+                method: method.clone(),
+                params: params.clone(),
+                error: error.clone(),
+            },
         }
     }
 }
@@ -81,9 +225,16 @@ impl PartialEq for Error {
         use self::Error::*;
         match (self, other) {
             (Unreachable, Unreachable) | (Internal, Internal) => true,
-            (Json(a), Json(b)) | (Transport(a), Transport(b)) => {
+            (Json(a), Json(b)) | (Transport(a), Transport(b)) | (Config(a), Config(b)) => {
                 a == b
             }
+            (ActorNotFound(a), ActorNotFound(b)) | (NonceTooLow(a), NonceTooLow(b)) => a == b,
+            (InsufficientFunds(a), InsufficientFunds(b)) | (PermissionDenied(a), PermissionDenied(b)) => a == b,
+            (TipSetNotFound(a), TipSetNotFound(b)) | (Estimation(a), Estimation(b)) => a == b,
+            (Reorganized(a), Reorganized(b)) | (Protocol(a), Protocol(b)) => a == b,
+            (Call { method: a, params: p, error: e }, Call { method: b, params: q, error: f }) => {
+                a == b && p == q && e == f
+            }
             (Rpc(a), Rpc(b)) => a == b,
             (Io(a), Io(b)) => a.kind() == b.kind(),
             (Signing(a), Signing(b)) => a == b,
@@ -91,3 +242,96 @@ impl PartialEq for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpc_core::ErrorCode;
+    use serde_json::json;
+    use tokio::runtime::Runtime;
+    use crate::Http;
+    use crate::api::{StateApi, MpoolApi, ChainApi};
+    use crate::testing::MockServer;
+    use crate::types::{Address, TipSetKey};
+
+    fn lotus_error(message: &str) -> RPCError {
+        RPCError {
+            code: ErrorCode::ServerError(1),
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    #[test]
+    fn typed_lotus_errors() {
+        let mut rt = Runtime::new().unwrap();
+        let server = rt.enter(MockServer::start);
+        server
+            .fail("StateGetActor", lotus_error("resolution lookup failed (t01234): resolve address t01234: actor not found"))
+            .fail("MpoolGetNonce", lotus_error("missing permission to invoke 'MpoolGetNonce' (need 'read')"))
+            .fail("ChainHead", lotus_error("something else"));
+        let http = Http::new(&server.url());
+        let addr = Address::new_id_addr(1234).unwrap();
+        let key: TipSetKey = serde_json::from_value(json!([])).unwrap();
+
+        let e = rt.block_on(http.state_get_actor(&addr, &key)).unwrap_err();
+        assert_eq!(e.method(), Some("Filecoin.StateGetActor"));
+        assert!(matches!(e.kind(), Error::ActorNotFound(_)));
+        assert!(!e.is_retryable());
+        assert_eq!(
+            e.to_string(),
+            "Filecoin.StateGetActor failed: Actor not found: \
+             resolution lookup failed (t01234): resolve address t01234: actor not found"
+        );
+        match e {
+            Error::Call { params, .. } => assert_eq!(params, vec![json!("t01234"), json!([])]),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let e = rt.block_on(http.mpool_get_nonce(&addr)).unwrap_err();
+        assert!(matches!(e.kind(), Error::PermissionDenied(_)));
+        let e = rt.block_on(http.chain_head()).unwrap_err();
+        assert_eq!(e.kind(), &Error::Rpc(lotus_error("something else")));
+
+        drop(server);
+        let e = rt.block_on(http.chain_head()).unwrap_err();
+        assert_eq!(e.method(), Some("Filecoin.ChainHead"));
+        assert!(e.is_retryable());
+
+        let retryable = vec![
+            Error::Unreachable,
+            Error::Transport("HTTP status 503 Service Unavailable".into()),
+            Error::Io(IoError::from(std::io::ErrorKind::ConnectionReset)),
+        ];
+        for e in retryable {
+            assert!(e.with_call("Filecoin.ChainHead", vec![]).is_retryable());
+        }
+        let final_errors = vec![
+            Error::Protocol("HTTP status 404 Not Found".into()),
+            Error::Protocol("Expected single, got batch".into()),
+            Error::Json("expected value".into()),
+            Error::Config("no endpoint".into()),
+            Error::Rpc(lotus_error("something else")),
+            Error::TipSetNotFound(String::new()),
+        ];
+        for e in final_errors {
+            assert!(!e.with_call("Filecoin.ChainHead", vec![]).is_retryable());
+        }
+    }
+
+    #[test]
+    fn rpc_error_mapping() {
+        let cases = vec![
+            ("mpool push: minimum expected nonce is 5: message nonce too low", Error::NonceTooLow(String::new())),
+            ("not enough funds including pending messages", Error::InsufficientFunds(String::new())),
+            ("loading tipset {bafy2bzace}: get block bafy2bzace: blockstore: block not found", Error::TipSetNotFound(String::new())),
+            ("get block bafy2bzace: ipld: could not find bafy2bzace", Error::TipSetNotFound(String::new())),
+            ("message bafy2bzace not found in tipset", Error::Rpc(lotus_error(""))),
+            ("estimating gas used: message execution failed: exit SysErrOutOfGas(7)", Error::Estimation(String::new())),
+        ];
+        for (message, expected) in cases {
+            let e = Error::from(lotus_error(message));
+            assert_eq!(std::mem::discriminant(&e), std::mem::discriminant(&expected), "{}", message);
+        }
+    }
+}
@@ -2,36 +2,54 @@ pub mod transports;
 pub mod error;
 pub mod api;
 pub mod types;
+pub mod wallet;
+pub mod message;
+pub mod chain;
 mod helper;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub use num_traits::cast::ToPrimitive;
-pub use transports::Http;
+pub use transports::{Http, Ws};
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use super::api::ChainApi;
     use tokio::runtime::Runtime;
+    use crate::testing::MockServer;
     use crate::types::tipset::TipSet;
     use cid::Cid;
     use std::convert::TryFrom;
-    use num_bigint::BigInt;
-    use num_traits::cast::ToPrimitive;
+    use crate::types::TokenAmount;
+    use serde_json::json;
 
     #[test]
     fn test() {
         let mut rt = Runtime::new().unwrap();
-        let http = Http::new("http://47.52.21.141:1234/rpc/v0");
+        let server = rt.enter(MockServer::start);
+        server
+            .fixture("ChainHead", json!({
+                "Cids": [{"/": "bafy2bzacec43cadndvrgpiq3lia65pbyj2t32jltlqp2oszlvknwn5wx3vyii"}],
+                "Blocks": [],
+                "Height": 149063,
+            }))
+            .fixture("ChainGetBlockMessages", json!({"BlsMessages": [], "SecpkMessages": [], "Cids": []}))
+            .fixture("ChainGetMessage", json!({
+                "Version": 0, "To": "t01", "From": "t01000", "Nonce": 5, "Value": "340282366920938463463374607431768211455",
+                "GasLimit": 1000, "GasFeeCap": "1", "GasPremium": "1", "Method": 0, "Params": "",
+            }));
+        let http = Http::new(&server.url());
         let ret:TipSet = rt.block_on(http.chain_head()).unwrap();
 
         println!("height {:?}",ret.height);
         let cids = ret.cids[0].clone();
         let ret = rt.block_on(http.chain_get_block_messages(&cids)).unwrap();
+        assert!(ret.cids.is_empty());
 
         let m_cid = Cid::try_from("bafy2bzacedxqegiy4be7m5pxif6hiam67evc37p4ci6tn33nkma4kvivkunhw").unwrap();
         let ret = rt.block_on(http.chain_get_message(&m_cid)).unwrap();
-        let val:BigInt = ret.value;
-        println!("result: {:?}", val);
-        let val_u128 = val.to_u128().unwrap();
-        println!("result: {:?}", val_u128);
+        let val: TokenAmount = ret.value;
+        println!("result: {}", val);
+        assert_eq!(val, TokenAmount::from_atto(u128::max_value()));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,307 @@
+use num_traits::ToPrimitive;
+use crate::api::{GasApi, MpoolApi, StateApi, WalletApi};
+use crate::error::{Error, Result};
+use crate::types::{Address, Bytes, MessageSendSpec, TipSetKey, TokenAmount, UnsignedMessage};
+
+/// The maximum gas of the messages of a block, and so of a message.
+pub const BLOCK_GAS_LIMIT: u64 = 10_000_000_000;
+
+/// Builder of an `UnsignedMessage`, filling the nonce and the gas through the node.
+///
+/// ```ignore
+/// let msg = MessageBuilder::new(from, to)
+///     .value("1.5 FIL".parse()?)
+///     .build(&http)
+///     .await?;
+/// let cid = wallet.push_message(&http, msg).await?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct MessageBuilder {
+    from: Address,
+    to: Address,
+    value: TokenAmount,
+    method: u64,
+    params: Bytes,
+    nonce: Option<u64>,
+    gas_limit: Option<u64>,
+    gas_fee_cap: Option<TokenAmount>,
+    gas_premium: Option<TokenAmount>,
+    max_fee: Option<TokenAmount>,
+    gas_overestimation: f64,
+    blocks_to_include: u64,
+    check_balance: bool,
+}
+
+impl MessageBuilder {
+    /// A transfer of nothing from `from` to `to`, see `value`, `method` and `params`.
+    pub fn new(from: Address, to: Address) -> Self {
+        Self {
+            from,
+            to,
+            value: TokenAmount::zero(),
+            method: 0,
+            params: Bytes::default(),
+            nonce: None,
+            gas_limit: None,
+            gas_fee_cap: None,
+            gas_premium: None,
+            max_fee: None,
+            gas_overestimation: 1.25,
+            blocks_to_include: 2,
+            check_balance: true,
+        }
+    }
+
+    pub fn value(mut self, value: TokenAmount) -> Self {
+        self.value = value;
+        self
+    }
+
+    pub fn method(mut self, method: u64) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// The CBOR encoded params of the method.
+    pub fn params<B: Into<Bytes>>(mut self, params: B) -> Self {
+        self.params = params.into();
+        self
+    }
+
+    /// Use `nonce` instead of the next nonce of the sender in the message pool.
+    pub fn nonce(mut self, nonce: u64) -> Self {
+        self.nonce = Some(nonce);
+        self
+    }
+
+    /// Use `gas_price` as both the fee cap and the premium, instead of estimating them.
+    pub fn gas_price(mut self, gas_price: TokenAmount) -> Self {
+        self.gas_fee_cap = Some(gas_price.clone());
+        self.gas_premium = Some(gas_price);
+        self
+    }
+
+    /// Use `gas_limit` instead of estimating it.
+    pub fn gas_limit(mut self, gas_limit: u64) -> Self {
+        self.gas_limit = Some(gas_limit);
+        self
+    }
+
+    /// Use `gas_fee_cap` instead of estimating it.
+    pub fn gas_fee_cap(mut self, gas_fee_cap: TokenAmount) -> Self {
+        self.gas_fee_cap = Some(gas_fee_cap);
+        self
+    }
+
+    /// Use `gas_premium` instead of estimating it.
+    pub fn gas_premium(mut self, gas_premium: TokenAmount) -> Self {
+        self.gas_premium = Some(gas_premium);
+        self
+    }
+
+    /// Cap the estimated fee of the message, instead of the default maximum fee of the node.
+    pub fn max_fee(mut self, max_fee: TokenAmount) -> Self {
+        self.max_fee = Some(max_fee);
+        self
+    }
+
+    /// Factor applied to the gas used by a trial run of the message, 1.25 by default.
+    pub fn gas_overestimation(mut self, factor: f64) -> Self {
+        self.gas_overestimation = factor;
+        self
+    }
+
+    /// Number of blocks the message should be included within, which drives the
+    /// estimated gas premium, 2 by default.
+    pub fn blocks_to_include(mut self, blocks: u64) -> Self {
+        self.blocks_to_include = blocks;
+        self
+    }
+
+    /// Skip checking that the balance of the sender covers the message.
+    pub fn skip_balance_check(mut self) -> Self {
+        self.check_balance = false;
+        self
+    }
+
+    /// Fill the nonce and the gas fields which weren't given, then check that the balance
+    /// of the sender covers the value plus the maximum gas cost of the message.
+    ///
+    /// The gas limit is estimated by a trial run of the message, and the premium for
+    /// `blocks_to_include`. The fee cap is left to `GasEstimateMessageGas`, which also caps
+    /// the fee to `max_fee`.
+    pub async fn build<A>(self, api: &A) -> Result<UnsignedMessage>
+        where
+            A: GasApi + MpoolApi + StateApi + WalletApi + Sync,
+    {
+        let nonce = match self.nonce {
+            Some(nonce) => nonce,
+            None => api.mpool_get_nonce(&self.from).await?,
+        };
+        let mut msg = UnsignedMessage {
+            version: 0,
+            to: self.to,
+            from: self.from,
+            nonce,
+            value: self.value,
+            gas_price: None,
+            gas_limit: BLOCK_GAS_LIMIT as i64,
+            gas_fee_cap: self.gas_fee_cap.unwrap_or_default(),
+            gas_premium: self.gas_premium.unwrap_or_default(),
+            method: self.method,
+            params: self.params,
+        };
+
+        msg.gas_limit = match self.gas_limit {
+            Some(gas_limit) => gas_limit as i64,
+            None => estimate_gas_limit(api, &msg, self.gas_overestimation).await? as i64,
+        };
+        if msg.gas_premium.is_zero() {
+            msg.gas_premium = api
+                .gas_estimate_gas_premium(self.blocks_to_include, &msg.from, msg.gas_limit, &TipSetKey::empty())
+                .await?;
+        }
+        // The node only fills the fields left to zero.
+        if msg.gas_fee_cap.is_zero() {
+            let spec = self.max_fee.map(|max_fee| MessageSendSpec { max_fee });
+            msg = api
+                .gas_estimate_message_gas(&msg, spec.as_ref(), &TipSetKey::empty())
+                .await?;
+        }
+
+        if self.check_balance {
+            let balance = api.wallet_balance(&msg.from).await?;
+            let required = &msg.value + &msg.required_funds();
+            if balance < required {
+                return Err(Error::InsufficientFunds(format!(
+                    "balance of {} is {}, the message needs {} ({} + gas {})",
+                    msg.from,
+                    balance,
+                    required,
+                    msg.value,
+                    msg.required_funds(),
+                )));
+            }
+        }
+        Ok(msg)
+    }
+}
+
+// Run the message on the head with the block gas limit, and scale the gas it used.
+async fn estimate_gas_limit<A>(api: &A, msg: &UnsignedMessage, overestimation: f64) -> Result<u64>
+    where
+        A: StateApi + Sync,
+{
+    let result = api.state_call(msg, &TipSetKey::empty()).await?;
+    let receipt = match result.msg_rct {
+        Some(receipt) if result.error.is_empty() && receipt.is_success() => receipt,
+        Some(receipt) if result.error.is_empty() => {
+            return Err(Error::Estimation(format!("message fails with {}", receipt.exit_code)));
+        }
+        _ => return Err(Error::Estimation(result.error)),
+    };
+    let gas_used = receipt
+        .gas_used
+        .to_u64()
+        .ok_or_else(|| Error::Estimation(format!("invalid gas used: {}", receipt.gas_used)))?;
+    Ok(((gas_used as f64 * overestimation) as u64).min(BLOCK_GAS_LIMIT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use tokio::runtime::Runtime;
+    use crate::Http;
+    use crate::testing::{MockServer, RecordedCall};
+
+    fn invoc_result(params: Vec<Value>, exit_code: i64, error: &str) -> Value {
+        json!({
+            "Msg": params[0],
+            "MsgRct": {"ExitCode": exit_code, "Return": "", "GasUsed": "1000"},
+            "Error": error,
+            "Duration": 1,
+        })
+    }
+
+    // Fills the zero fee cap like Lotus.
+    fn estimate_message_gas(params: Vec<Value>) -> Value {
+        let mut msg = params[0].clone();
+        if msg["GasFeeCap"] == json!("0") {
+            msg["GasFeeCap"] = json!("100");
+        }
+        msg
+    }
+
+    #[test]
+    fn build_message() {
+        let mut rt = Runtime::new().unwrap();
+        let server = rt.enter(MockServer::start);
+        server
+            .fixture("MpoolGetNonce", json!(7))
+            .handle("StateCall", |params| Ok(invoc_result(params, 0, "")))
+            .fixture("GasEstimateGasPremium", json!("10"))
+            .handle("GasEstimateMessageGas", |params| Ok(estimate_message_gas(params)))
+            .fixture("WalletBalance", json!("1000125000"));
+        let http = Http::new(&server.url());
+        let from = Address::new_id_addr(1000).unwrap();
+        let to = Address::new_id_addr(1001).unwrap();
+
+        let builder = MessageBuilder::new(from, to).value(TokenAmount::from_atto(1_000_000_000));
+        let msg = rt.block_on(builder.clone().build(&http)).unwrap();
+        assert_eq!(msg.nonce, 7);
+        assert_eq!(msg.gas_limit, 1250);
+        assert_eq!(msg.gas_fee_cap, TokenAmount::from_atto(100));
+        assert_eq!(msg.gas_premium, TokenAmount::from_atto(10));
+        assert_eq!(msg.value, TokenAmount::from_atto(1_000_000_000));
+        let calls = server.take_calls();
+        assert_eq!(calls[1].params[0]["GasLimit"], json!(BLOCK_GAS_LIMIT));
+        assert_eq!(calls[2], RecordedCall {
+            method: "Filecoin.GasEstimateGasPremium".into(),
+            params: vec![json!(2), json!("t01000"), json!(1250), json!([])],
+        });
+        assert_eq!(calls[3].method, "Filecoin.GasEstimateMessageGas");
+        assert_eq!(calls[3].params[1], Value::Null);
+
+        // 100 attoFIL short.
+        let msg = rt.block_on(builder.clone().gas_limit(1251).max_fee(TokenAmount::from_fil(1)).build(&http));
+        assert!(matches!(msg, Err(Error::InsufficientFunds(_))));
+        assert_eq!(server.take_calls()[2].params[1], json!({"MaxFee": "1000000000000000000"}));
+        let msg = rt.block_on(builder.clone().gas_limit(1251).skip_balance_check().build(&http)).unwrap();
+        assert_eq!(msg.gas_limit, 1251);
+        server.take_calls();
+
+        let msg = rt.block_on(builder.nonce(3).gas_price(TokenAmount::from_atto(2)).gas_limit(10).build(&http)).unwrap();
+        assert_eq!((msg.nonce, msg.gas_limit), (3, 10));
+        assert_eq!((msg.gas_fee_cap.clone(), msg.gas_premium.clone()), (TokenAmount::from_atto(2), TokenAmount::from_atto(2)));
+        assert_eq!(msg.gas_price, None);
+        let methods = server.take_calls().into_iter().map(|call| call.method).collect::<Vec<_>>();
+        assert_eq!(methods, vec!["Filecoin.WalletBalance"]);
+    }
+
+    #[test]
+    fn build_failing_message() {
+        let mut rt = Runtime::new().unwrap();
+        let server = rt.enter(MockServer::start);
+        server
+            .fixture("MpoolGetNonce", json!(0))
+            .handle("StateCall", |params| Ok(invoc_result(params, 16, "")));
+        let http = Http::new(&server.url());
+        let builder = MessageBuilder::new(Address::new_id_addr(1000).unwrap(), Address::new_id_addr(1).unwrap());
+        let msg = rt.block_on(builder.clone().method(2).build(&http));
+        assert_eq!(msg, Err(Error::Estimation("message fails with ErrIllegalArgument(16): illegal argument".into())));
+
+        server.handle("StateCall", |params| Ok(invoc_result(params, 0, "actor not found")));
+        let msg = rt.block_on(builder.clone().build(&http));
+        assert_eq!(msg, Err(Error::Estimation("actor not found".into())));
+
+        server.fail("GasEstimateMessageGas", jsonrpc_core::Error {
+            code: jsonrpc_core::ErrorCode::ServerError(1),
+            message: "estimating gas used: message execution failed: exit ErrIllegalArgument(16)".into(),
+            data: None,
+        });
+        let builder = builder.gas_limit(1000).gas_premium(TokenAmount::from_atto(1));
+        let e = rt.block_on(builder.method(2).build(&http)).unwrap_err();
+        assert!(matches!(e.kind(), Error::Estimation(_)));
+    }
+}
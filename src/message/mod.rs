@@ -0,0 +1,9 @@
+//! Preparing messages for the chain: filling the nonce and the gas of a message from the node,
+//! then following it until it's confirmed.
+mod builder;
+mod nonce;
+mod sender;
+
+pub use builder::{MessageBuilder, BLOCK_GAS_LIMIT};
+pub use nonce::NonceManager;
+pub use sender::{MessageSender, SenderConfig, SendOutcome};
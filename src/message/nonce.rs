@@ -0,0 +1,254 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::api::MpoolApi;
+use crate::error::{Error, Result};
+use crate::types::Address;
+
+/// The nonces of one sender.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct Nonces {
+    /// The nonce handed out after the released ones.
+    next: u64,
+    /// Nonces below `next` which were handed out but never pushed.
+    #[serde(default)]
+    released: BTreeSet<u64>,
+    /// Whether `next` has been checked against the message pool since the start.
+    #[serde(skip)]
+    synced: bool,
+}
+
+impl Nonces {
+    // Never goes back below the nonces handed out, even if the pool is behind.
+    fn sync(&mut self, pool_nonce: u64) {
+        self.next = self.next.max(pool_nonce);
+        self.released = self.released.split_off(&pool_nonce);
+        self.synced = true;
+    }
+
+    fn take(&mut self) -> u64 {
+        match self.released.iter().next().copied() {
+            Some(nonce) => {
+                self.released.remove(&nonce);
+                nonce
+            }
+            None => {
+                self.next += 1;
+                self.next - 1
+            }
+        }
+    }
+
+    fn release(&mut self, nonce: u64) {
+        if nonce >= self.next {
+            return;
+        }
+        self.released.insert(nonce);
+        // Shrink `next` back over the released nonces at the end.
+        while self.next > 0 && self.released.remove(&(self.next - 1)) {
+            self.next -= 1;
+        }
+    }
+}
+
+/// Hands out the nonces of senders shared by concurrent tasks, so that each message
+/// gets its own nonce without asking the node for each of them.
+///
+/// The nonces of an address are seeded from `mpool_get_nonce`, and resynced when a push
+/// fails because the nonce is too low or already in the pool. The nonce of a message
+/// which failed to push for another reason is released, to be handed out again.
+///
+/// With `open`, the nonces are persisted to a file at each change, so that a restart
+/// doesn't hand out the nonces of messages which aren't in the pool yet. The file is
+/// written and synced to disk by the calling task, which blocks its thread meanwhile.
+#[derive(Debug, Default)]
+pub struct NonceManager {
+    nonces: Mutex<HashMap<Address, Nonces>>,
+    path: Option<PathBuf>,
+}
+
+impl NonceManager {
+    /// A manager keeping the nonces in memory only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A manager persisting the nonces to the JSON file at `path`, loaded if it exists.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let nonces = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            nonces: Mutex::new(nonces),
+            path: Some(path),
+        })
+    }
+
+    /// Hand out the next nonce of `addr`, seeding it from the message pool the first time.
+    pub async fn next<A>(&self, api: &A, addr: &Address) -> Result<u64>
+        where
+            A: MpoolApi + Sync,
+    {
+        if !self.is_synced(addr) {
+            let pool_nonce = api.mpool_get_nonce(addr).await?;
+            self.update(addr, |nonces| nonces.sync(pool_nonce))?;
+        }
+        self.update(addr, Nonces::take)
+    }
+
+    /// Release a nonce handed out by `next` whose message wasn't pushed, so that it's
+    /// handed out again instead of leaving a gap.
+    pub fn release(&self, addr: &Address, nonce: u64) -> Result<()> {
+        self.update(addr, |nonces| nonces.release(nonce))
+    }
+
+    /// Resync the nonces of `addr` with the message pool, returning the nonce of the pool.
+    ///
+    /// Unlike the sync at the first `next`, this also goes back to the nonce of the pool if
+    /// it's lower, e.g. after pushed messages were dropped from the pool.
+    pub async fn resync<A>(&self, api: &A, addr: &Address) -> Result<u64>
+        where
+            A: MpoolApi + Sync,
+    {
+        let pool_nonce = api.mpool_get_nonce(addr).await?;
+        self.update(addr, |nonces| {
+            *nonces = Nonces::default();
+            nonces.sync(pool_nonce);
+        })?;
+        Ok(pool_nonce)
+    }
+
+    /// Record the result of pushing the message with a nonce handed out by `next`.
+    ///
+    /// If the nonce was too low or already used in the pool, the nonces of `addr` are
+    /// synced again at the next `next`. If the push failed otherwise, the nonce is released.
+    pub fn complete<T>(&self, addr: &Address, nonce: u64, result: &Result<T>) -> Result<()> {
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) if is_nonce_conflict(e) => self.update(addr, |nonces| nonces.synced = false),
+            Err(_) => self.release(addr, nonce),
+        }
+    }
+
+    fn is_synced(&self, addr: &Address) -> bool {
+        let nonces = self.nonces.lock().expect("lock poisoned");
+        matches!(nonces.get(addr), Some(nonces) if nonces.synced)
+    }
+
+    // Apply `f` to the nonces of `addr`, then persist them.
+    fn update<T, F>(&self, addr: &Address, f: F) -> Result<T>
+        where
+            F: FnOnce(&mut Nonces) -> T,
+    {
+        let mut nonces = self.nonces.lock().expect("lock poisoned");
+        let result = f(nonces.entry(addr.clone()).or_default());
+        if let Some(path) = &self.path {
+            // Replace the file atomically, a crash leaves either the previous nonces or these.
+            // Written under the lock, so that the last write has the last nonces.
+            let tmp = path.with_extension("tmp");
+            let mut file = fs::File::create(&tmp)?;
+            file.write_all(&serde_json::to_vec(&*nonces)?)?;
+            file.sync_all()?;
+            fs::rename(&tmp, path)?;
+        }
+        Ok(result)
+    }
+}
+
+/// Whether the push failed because the nonce was already used.
+pub(crate) fn is_nonce_conflict(e: &Error) -> bool {
+    match e.kind() {
+        Error::NonceTooLow(_) => true,
+        Error::Rpc(e) => e.message.contains("already in mpool"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use futures::future::join_all;
+    use jsonrpc_core::ErrorCode;
+    use serde_json::json;
+    use tokio::runtime::Runtime;
+    use crate::Http;
+    use crate::testing::MockServer;
+
+    fn pool(server: &MockServer, nonce: u64) -> Arc<AtomicU64> {
+        let pool_nonce = Arc::new(AtomicU64::new(nonce));
+        let handler_nonce = pool_nonce.clone();
+        server.handle("MpoolGetNonce", move |_| Ok(json!(handler_nonce.load(Ordering::Acquire))));
+        pool_nonce
+    }
+
+    #[test]
+    fn concurrent_nonces() {
+        let mut rt = Runtime::new().unwrap();
+        let server = rt.enter(MockServer::start);
+        let pool_nonce = pool(&server, 5);
+        let http = Http::new(&server.url());
+        let manager = NonceManager::new();
+        let addr = Address::new_id_addr(1000).unwrap();
+
+        let mut nonces = rt
+            .block_on(join_all((0..10).map(|_| manager.next(&http, &addr))))
+            .into_iter()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        nonces.sort();
+        assert_eq!(nonces, (5..15).collect::<Vec<_>>());
+
+        // The gap of a failed push is filled first, the last nonce just goes back.
+        let failed: Result<()> = Err(Error::Transport("connection reset".into()));
+        manager.complete(&addr, 7, &failed).unwrap();
+        manager.complete(&addr, 14, &failed).unwrap();
+        manager.complete(&addr, 8, &Ok(())).unwrap();
+        assert_eq!(rt.block_on(manager.next(&http, &addr)), Ok(7));
+        assert_eq!(rt.block_on(manager.next(&http, &addr)), Ok(14));
+
+        // Another sender used the address.
+        pool_nonce.store(20, Ordering::Release);
+        let conflict: Result<()> = Err(Error::Rpc(jsonrpc_core::Error {
+            code: ErrorCode::ServerError(1),
+            message: "message with nonce 15 already in mpool".into(),
+            data: None,
+        }));
+        manager.complete(&addr, 15, &conflict).unwrap();
+        assert_eq!(rt.block_on(manager.next(&http, &addr)), Ok(20));
+
+        pool_nonce.store(3, Ordering::Release);
+        assert_eq!(rt.block_on(manager.resync(&http, &addr)), Ok(3));
+        assert_eq!(rt.block_on(manager.next(&http, &addr)), Ok(3));
+    }
+
+    #[test]
+    fn persisted_nonces() {
+        let path = std::env::temp_dir().join(format!("lotus-api-nonces-{}.json", std::process::id()));
+        let mut rt = Runtime::new().unwrap();
+        let server = rt.enter(MockServer::start);
+        pool(&server, 5);
+        let http = Http::new(&server.url());
+        let addr = Address::new_id_addr(1000).unwrap();
+
+        let manager = NonceManager::open(&path).unwrap();
+        for expected in 5..8 {
+            assert_eq!(rt.block_on(manager.next(&http, &addr)), Ok(expected));
+        }
+        manager.release(&addr, 6).unwrap();
+        drop(manager);
+
+        // The pool is behind the nonces handed out before the restart.
+        let manager = NonceManager::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(rt.block_on(manager.next(&http, &addr)), Ok(6));
+        assert_eq!(rt.block_on(manager.next(&http, &addr)), Ok(8));
+    }
+}
@@ -0,0 +1,334 @@
+use std::time::{Duration, Instant};
+use log::{debug, info, warn};
+use crate::api::{ChainApi, MpoolApi, StateApi, WalletApi};
+use crate::error::Result;
+use crate::types::{ChainEpoch, Cid, MessageReceipt, SignedMessage, TipSetKey, TokenAmount, UnsignedMessage};
+use crate::wallet::LocalWallet;
+use super::nonce::is_nonce_conflict;
+
+/// Configuration of the `MessageSender`.
+#[derive(Clone, Debug)]
+pub struct SenderConfig {
+    /// Epochs from the tipset executing the message to the head before it's confirmed,
+    /// null rounds included.
+    pub confidence: ChainEpoch,
+    /// Delay between two polls of the head of the chain.
+    pub poll_interval: Duration,
+    /// Epochs a message stays pending before it's replaced with a higher premium.
+    pub stuck_epochs: ChainEpoch,
+    /// Factor applied to the premium of a stuck message, raised to the 1.25 required by
+    /// Lotus if lower.
+    pub premium_bump: f64,
+    /// Maximum number of replacements of a message.
+    pub max_replacements: usize,
+    /// How long to wait for the confirmation.
+    pub timeout: Duration,
+}
+
+// The lowest premium bump of a replacement accepted by the message pool of Lotus.
+const MIN_PREMIUM_BUMP: f64 = 1.25;
+
+impl Default for SenderConfig {
+    fn default() -> Self {
+        Self {
+            confidence: 5,
+            poll_interval: Duration::from_secs(10),
+            stuck_epochs: 10,
+            premium_bump: MIN_PREMIUM_BUMP,
+            max_replacements: 3,
+            timeout: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// The final outcome of sending a message.
+#[derive(Clone, Debug)]
+pub enum SendOutcome {
+    /// The message, or one of its replacements, was executed `confidence` epochs below the head.
+    /// The receipt tells whether the execution succeeded.
+    Confirmed {
+        /// The executed message, which may be a replacement.
+        message: SignedMessage,
+        cid: Cid,
+        receipt: MessageReceipt,
+        /// The key of the tipset where the message was executed.
+        tipset: TipSetKey,
+        height: ChainEpoch,
+    },
+    /// The message wasn't confirmed before the timeout, the pushed messages may still
+    /// be in the pool.
+    TimedOut {
+        /// The CIDs of the message and its replacements, in the order they were pushed.
+        cids: Vec<Cid>,
+        /// The last message pushed, to keep following it or replace it.
+        message: SignedMessage,
+    },
+}
+
+/// Pushes messages and follows them until they are confirmed on chain, replacing the
+/// messages stuck in the pool with the same nonce and a higher premium.
+///
+/// Messages are signed by the wallet of the node, or by a `LocalWallet`.
+#[derive(Debug, Default)]
+pub struct MessageSender {
+    wallet: Option<LocalWallet>,
+    config: SenderConfig,
+}
+
+impl MessageSender {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_config(mut config: SenderConfig) -> Self {
+        config.premium_bump = config.premium_bump.max(MIN_PREMIUM_BUMP);
+        Self { wallet: None, config }
+    }
+
+    /// Sign the messages with `wallet` instead of the wallet of the node.
+    pub fn wallet(mut self, wallet: LocalWallet) -> Self {
+        self.wallet = Some(wallet);
+        self
+    }
+
+    /// Push `msg`, whose nonce and gas must be set (see `MessageBuilder`), then wait for
+    /// its confirmation.
+    ///
+    /// Fails if the message can't be pushed. The errors of the node while waiting, and of
+    /// pushing a replacement, are logged, and the pushed messages are still followed.
+    pub async fn send<A>(&self, api: &A, msg: UnsignedMessage) -> Result<SendOutcome>
+        where
+            A: ChainApi + MpoolApi + StateApi + WalletApi + Sync,
+    {
+        let deadline = Instant::now() + self.config.timeout;
+        let mut pending = Pending {
+            pushed: vec![self.push(api, msg).await?],
+            pushed_at: None,
+            last_height: None,
+        };
+
+        loop {
+            match self.poll(api, &mut pending).await {
+                Ok(Some(outcome)) => return Ok(outcome),
+                Ok(None) => {}
+                Err(e) => warn!("Failed to follow {}: {}", pending.pushed[0].0, e),
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                let message = pending.pushed.last().expect("the message was pushed; qed").1.clone();
+                return Ok(SendOutcome::TimedOut {
+                    cids: pending.pushed.into_iter().map(|(cid, _)| cid).collect(),
+                    message,
+                });
+            }
+            tokio::time::delay_for(self.config.poll_interval.min(deadline - now)).await;
+        }
+    }
+
+    // Look for the pushed messages when the head changed, and replace the last one if it's
+    // stuck. A poll which fails is done again, even at the same head.
+    async fn poll<A>(&self, api: &A, pending: &mut Pending) -> Result<Option<SendOutcome>>
+        where
+            A: ChainApi + MpoolApi + StateApi + WalletApi + Sync,
+    {
+        let head = api.chain_head().await?;
+        if pending.last_height == Some(head.height) {
+            return Ok(None);
+        }
+        let pushed_at = *pending.pushed_at.get_or_insert(head.height);
+
+        let mut included = false;
+        // The latest replacement is the most likely to be executed.
+        for (cid, message) in pending.pushed.iter().rev() {
+            let lookup = match api.state_search_msg(cid).await? {
+                Some(lookup) => lookup,
+                None => continue,
+            };
+            if head.height - lookup.height >= self.config.confidence {
+                return Ok(Some(SendOutcome::Confirmed {
+                    message: message.clone(),
+                    cid: cid.clone(),
+                    receipt: lookup.receipt,
+                    tipset: lookup.tipset,
+                    height: lookup.height,
+                }));
+            }
+            included = true;
+            break;
+        }
+
+        let replacements = pending.pushed.len() - 1;
+        if !included
+            && head.height - pushed_at >= self.config.stuck_epochs
+            && replacements < self.config.max_replacements
+        {
+            let (cid, last) = pending.pushed.last().expect("the message was pushed; qed");
+            let replacement = self.replacement(&last.message);
+            info!("Message {} is stuck since epoch {}, replacing it", cid, pushed_at);
+            match self.push(api, replacement).await {
+                Ok(replacement) => pending.pushed.push(replacement),
+                // One of the messages was executed meanwhile.
+                Err(e) if is_nonce_conflict(&e) => debug!("Replacement of {} rejected: {}", cid, e),
+                Err(e) => warn!("Failed to replace {}: {}", cid, e),
+            }
+            pending.pushed_at = Some(head.height);
+        }
+        pending.last_height = Some(head.height);
+        Ok(None)
+    }
+
+    async fn push<A>(&self, api: &A, msg: UnsignedMessage) -> Result<(Cid, SignedMessage)>
+        where
+            A: MpoolApi + WalletApi + Sync,
+    {
+        let signed = match &self.wallet {
+            Some(wallet) => wallet.sign_message(msg)?,
+            None => api.wallet_sign_message(&msg.from, &msg).await?,
+        };
+        let cid = api.mpool_push(&signed).await?;
+        debug!("Pushed message {} with nonce {}", cid, signed.message.nonce);
+        Ok((cid, signed))
+    }
+
+    // The same message with the premium raised by `premium_bump`, and the fee cap raised
+    // to the premium if lower. A message with a single gas price takes the current shape.
+    fn replacement(&self, msg: &UnsignedMessage) -> UnsignedMessage {
+        let per_mille = (self.config.premium_bump * 1000.0).ceil() as u64;
+        let premium = TokenAmount::from_atto(msg.gas_premium.atto() * per_mille / 1000u64 + 1u64);
+        let mut replacement = msg.clone();
+        replacement.gas_price = None;
+        if replacement.gas_fee_cap < premium {
+            replacement.gas_fee_cap = premium.clone();
+        }
+        replacement.gas_premium = premium;
+        replacement
+    }
+}
+
+// The messages pushed by `send`, with the replacements.
+struct Pending {
+    pushed: Vec<(Cid, SignedMessage)>,
+    /// The height of the head when the last message was pushed.
+    pushed_at: Option<ChainEpoch>,
+    /// The height of the head at the last poll.
+    last_height: Option<ChainEpoch>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicI64, Ordering};
+    use serde_json::{json, Value};
+    use tokio::runtime::Runtime;
+    use crate::Http;
+    use crate::testing::{MockChain, MockServer};
+    use crate::types::{Address, CidJson, ExitCode};
+    use crate::types::keystore::KeyType;
+
+    fn config() -> SenderConfig {
+        SenderConfig {
+            confidence: 2,
+            poll_interval: Duration::from_millis(5),
+            stuck_epochs: 3,
+            premium_bump: 1.25,
+            max_replacements: 1,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    // A chain from epoch 100, advancing one epoch per `ChainHead` call, returning the pushed messages.
+    fn chain(server: &MockServer) -> Arc<Mutex<Vec<SignedMessage>>> {
+        let chain = MockChain::new();
+        chain.add("99", 99, None);
+        let pushed = Arc::new(Mutex::new(Vec::new()));
+        let pool = pushed.clone();
+        server
+            .chain(&chain)
+            .handle("ChainHead", move |_| Ok(json!(chain.grow())))
+            .handle("MpoolPush", move |params| {
+                let signed: SignedMessage = serde_json::from_value(params[0].clone()).unwrap();
+                let cid = signed.cid();
+                pool.lock().unwrap().push(signed);
+                Ok(serde_json::to_value(CidJson(cid)).unwrap())
+            });
+        pushed
+    }
+
+    // The shared message fixture, with a premium to bump.
+    fn message(from: Address) -> UnsignedMessage {
+        UnsignedMessage {
+            nonce: 3,
+            gas_fee_cap: TokenAmount::from_atto(100),
+            gas_premium: TokenAmount::from_atto(100),
+            ..crate::types::message::message(from)
+        }
+    }
+
+    #[test]
+    fn replace_stuck_message() {
+        let mut rt = Runtime::new().unwrap();
+        let server = rt.enter(MockServer::start);
+        let pushed = chain(&server);
+        let mut wallet = LocalWallet::new();
+        let from = wallet.generate(KeyType::Secp256k1).unwrap();
+
+        // Only the replacement gets executed, the first search fails.
+        let searches = AtomicI64::new(0);
+        server.handle("StateSearchMsg", move |params| {
+            if searches.fetch_add(1, Ordering::AcqRel) == 0 {
+                return Err(jsonrpc_core::Error::internal_error());
+            }
+            let cid: CidJson = serde_json::from_value(params[0].clone()).unwrap();
+            let pushed = pushed.lock().unwrap();
+            Ok(match pushed.get(1) {
+                Some(replacement) if replacement.cid() == cid.0 => json!({
+                    "Receipt": {"ExitCode": 0, "Return": "", "GasUsed": "500"},
+                    "TipSet": [{"/": "bafy2bzacebwut2il7udv5d3yzscpwbomvj5ocq6lkxh4kcusiy5juesvpun4c"}],
+                    "Height": 105,
+                }),
+                _ => Value::Null,
+            })
+        });
+        // A bump below the minimum of Lotus would be rejected.
+        let sender = MessageSender::with_config(SenderConfig { premium_bump: 1.1, ..config() }).wallet(wallet);
+        let http = Http::new(&server.url());
+        match rt.block_on(sender.send(&http, message(from))).unwrap() {
+            SendOutcome::Confirmed { message, receipt, height, .. } => {
+                assert_eq!(message.message.nonce, 3);
+                assert_eq!(message.message.gas_premium, TokenAmount::from_atto(126));
+                assert_eq!(message.message.gas_fee_cap, TokenAmount::from_atto(126));
+                assert_eq!(receipt.exit_code, ExitCode::Ok);
+                assert_eq!(height, 105);
+            }
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        }
+        let pushes = server.calls().into_iter().filter(|call| call.method == "Filecoin.MpoolPush").count();
+        assert_eq!(pushes, 2);
+    }
+
+    #[test]
+    fn timeout() {
+        let mut rt = Runtime::new().unwrap();
+        let server = rt.enter(MockServer::start);
+        chain(&server);
+        server
+            .fixture("StateSearchMsg", Value::Null)
+            .handle("WalletSignMessage", |params| {
+                let message: UnsignedMessage = serde_json::from_value(params[1].clone()).unwrap();
+                Ok(json!({"Message": message, "Signature": {"Type": 2, "Data": base64::encode(&[0u8; 96][..])}}))
+            });
+        let sender = MessageSender::with_config(SenderConfig { timeout: Duration::from_millis(100), ..config() });
+        let http = Http::new(&server.url());
+        match rt.block_on(sender.send(&http, message(Address::new_id_addr(1000).unwrap()))).unwrap() {
+            // The message and its single replacement.
+            SendOutcome::TimedOut { cids, message } => {
+                assert_eq!(cids.len(), 2);
+                assert_eq!(message.message.gas_premium, TokenAmount::from_atto(126));
+            }
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        }
+        assert!(server.calls().iter().any(|call| call.method == "Filecoin.WalletSignMessage"));
+    }
+}
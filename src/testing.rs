@@ -0,0 +1,414 @@
+//! In-process mock of the Lotus JSON-RPC server, for testing code written against the
+//! API traits without a node.
+//!
+//! ```ignore
+//! let server = MockServer::start();
+//! server.fixture("ChainHead", json!({"Cids": [], "Blocks": [], "Height": 10}));
+//! server.handle("WalletHas", |params| Ok(Value::Bool(params[0] == "t01000")));
+//!
+//! let http = Http::new(&server.url());
+//! assert_eq!(http.chain_head().await?.height, 10);
+//! ```
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use cid::{Cid, Codec};
+use futures::channel::oneshot;
+use hyper::{Body, Response, Server};
+use hyper::service::{make_service_fn, service_fn};
+use jsonrpc_core::{Call, Error as RpcError, Failure, Id, MethodCall, Output, Params, Request, Success, Version};
+use log::warn;
+use multihash::Blake2b256;
+use serde_json::{json, Value};
+use crate::error::{Error, Result};
+use crate::types::{ChainEpoch, CidJson, TipSet, TipSetKey};
+
+const NAMESPACE: &str = "Filecoin.";
+
+/// Handler of one method, called with the params of the request.
+pub type Handler = Arc<dyn Fn(Vec<Value>) -> std::result::Result<Value, RpcError> + Send + Sync>;
+
+/// A call received by the server.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedCall {
+    /// Full method name, e.g. `Filecoin.ChainHead`.
+    pub method: String,
+    pub params: Vec<Value>,
+}
+
+#[derive(Default)]
+struct State {
+    handlers: HashMap<String, Handler>,
+    calls: Vec<RecordedCall>,
+}
+
+/// Local HTTP JSON-RPC server answering `Filecoin.*` methods from programmable handlers.
+///
+/// Methods without a handler fail with `Method not found`. The server is stopped on drop.
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockServer {
+    /// Start the server on an ephemeral port of localhost.
+    ///
+    /// Must be called within a tokio runtime, which drives the server.
+    pub fn start() -> Self {
+        let state = Arc::new(Mutex::new(State::default()));
+        let service_state = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let state = state.clone();
+                    async move {
+                        let body = hyper::body::to_bytes(request.into_body()).await?;
+                        let response = handle_body(&state, &body);
+                        Ok::<_, hyper::Error>(Response::new(Body::from(response)))
+                    }
+                }))
+            }
+        });
+
+        let (shutdown, receiver) = oneshot::channel::<()>();
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(async move {
+            let server = server.with_graceful_shutdown(async {
+                let _ = receiver.await;
+            });
+            if let Err(e) = server.await {
+                warn!("Mock server error: {:?}", e);
+            }
+        });
+
+        Self {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+        }
+    }
+
+    /// Local address the server is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Endpoint to give to the `Http` transport.
+    pub fn url(&self) -> String {
+        format!("http://{}/rpc/v0", self.addr)
+    }
+
+    /// Answer `method` with `handler`, the `Filecoin.` namespace may be omitted.
+    pub fn handle<F>(&self, method: &str, handler: F) -> &Self
+        where
+            F: Fn(Vec<Value>) -> std::result::Result<Value, RpcError> + Send + Sync + 'static,
+    {
+        let method = method.trim_start_matches(NAMESPACE).to_string();
+        self.state.lock().expect("lock poisoned").handlers.insert(method, Arc::new(handler));
+        self
+    }
+
+    /// Answer `method` with `result` whatever the params.
+    pub fn fixture(&self, method: &str, result: Value) -> &Self {
+        self.handle(method, move |_| Ok(result.clone()))
+    }
+
+    /// Answer `method` with `error` whatever the params.
+    pub fn fail(&self, method: &str, error: RpcError) -> &Self {
+        self.handle(method, move |_| Err(error.clone()))
+    }
+
+    /// Load recorded results from a JSON file, an object of method names to results, e.g.
+    /// `{"ChainHead": {...}, "WalletBalance": "1000"}`.
+    pub fn fixtures<P: AsRef<Path>>(&self, path: P) -> Result<&Self> {
+        let fixtures = std::fs::read_to_string(path)?;
+        match serde_json::from_str(&fixtures)? {
+            Value::Object(fixtures) => {
+                for (method, result) in fixtures {
+                    self.fixture(&method, result);
+                }
+                Ok(self)
+            }
+            _ => Err(Error::Json("Expected an object of method results".into())),
+        }
+    }
+
+    /// Answer `ChainHead`, `ChainGetTipSet`, `ChainGetTipSetByHeight` and `ChainGetPath`
+    /// from `chain`, which may still change.
+    pub fn chain(&self, chain: &MockChain) -> &Self {
+        let (head, by_key, by_height, path) = (chain.clone(), chain.clone(), chain.clone(), chain.clone());
+        self.handle("ChainHead", move |_| Ok(json!(head.head())))
+            .handle("ChainGetTipSet", move |params| {
+                let key = tipset_key(&params[0])?;
+                Ok(json!(by_key.tipset(key.cids()[0].clone())))
+            })
+            .handle("ChainGetTipSetByHeight", move |params| {
+                let height = serde_json::from_value(params[0].clone()).map_err(invalid_params)?;
+                let key = tipset_key(&params[1])?;
+                Ok(json!(by_height.tipset_by_height(height, key.cids()[0].clone())))
+            })
+            .handle("ChainGetPath", move |params| {
+                let (from, to) = (tipset_key(&params[0])?, tipset_key(&params[1])?);
+                Ok(path.path(from.cids()[0].clone(), to.cids()[0].clone()))
+            })
+    }
+
+    /// Calls received so far, in order.
+    pub fn calls(&self) -> Vec<RecordedCall> {
+        self.state.lock().expect("lock poisoned").calls.clone()
+    }
+
+    /// Take the calls received so far, in order.
+    pub fn take_calls(&self) -> Vec<RecordedCall> {
+        std::mem::take(&mut self.state.lock().expect("lock poisoned").calls)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+fn handle_body(state: &Mutex<State>, body: &[u8]) -> String {
+    let response = match serde_json::from_slice::<Request>(body) {
+        Ok(Request::Single(call)) => handle_call(state, call).map(jsonrpc_core::Response::Single),
+        Ok(Request::Batch(calls)) => Some(jsonrpc_core::Response::Batch(
+            calls.into_iter().filter_map(|call| handle_call(state, call)).collect(),
+        )),
+        Err(_) => Some(jsonrpc_core::Response::Single(failure(Id::Null, RpcError::parse_error()))),
+    };
+    response
+        .map(|response| serde_json::to_string(&response).expect("Serialize `Response` never fails"))
+        .unwrap_or_default()
+}
+
+fn handle_call(state: &Mutex<State>, call: Call) -> Option<Output> {
+    let MethodCall { method, params, id, .. } = match call {
+        Call::MethodCall(call) => call,
+        // Notifications aren't answered.
+        Call::Notification(_) => return None,
+        Call::Invalid { id } => return Some(failure(id, RpcError::invalid_request())),
+    };
+    let params = match params {
+        Params::Array(params) => params,
+        Params::None => vec![],
+        Params::Map(_) => return Some(failure(id, RpcError::invalid_params("Expected positional params"))),
+    };
+
+    // The lock is released before calling the handler, which may call the server back.
+    let handler = {
+        let mut state = state.lock().expect("lock poisoned");
+        state.calls.push(RecordedCall { method: method.clone(), params: params.clone() });
+        method.strip_prefix(NAMESPACE).and_then(|method| state.handlers.get(method)).cloned()
+    };
+    Some(match handler {
+        Some(handler) => match handler(params) {
+            Ok(result) => Output::Success(Success { jsonrpc: Some(Version::V2), result, id }),
+            Err(error) => failure(id, error),
+        },
+        None => failure(id, RpcError::method_not_found()),
+    })
+}
+
+fn tipset_key(param: &Value) -> std::result::Result<TipSetKey, RpcError> {
+    match serde_json::from_value::<TipSetKey>(param.clone()) {
+        Ok(key) if !key.is_empty() => Ok(key),
+        Ok(_) => Err(RpcError::invalid_params("Expected a tipset key")),
+        Err(e) => Err(invalid_params(e)),
+    }
+}
+
+fn invalid_params(e: serde_json::Error) -> RpcError {
+    RpcError::invalid_params(e.to_string())
+}
+
+fn failure(id: Id, error: RpcError) -> Output {
+    Output::Failure(Failure { jsonrpc: Some(Version::V2), error, id })
+}
+
+/// A synthetic chain of single block tipsets, named by the tests, for `MockServer::chain`.
+///
+/// The clones share the tipsets, so that the chain can grow while it's served.
+///
+/// ```ignore
+/// let chain = MockChain::new();
+/// chain.add("g", 0, None);
+/// chain.add("a1", 1, Some("g"));
+/// chain.add("b1", 2, Some("g"));
+/// server.chain(&chain);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MockChain {
+    state: Arc<Mutex<ChainState>>,
+}
+
+#[derive(Debug, Default)]
+struct ChainState {
+    tipsets: HashMap<Cid, TipSet>,
+    head: Option<Cid>,
+}
+
+impl MockChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The CID of the tipset named `name`, and of its block.
+    pub fn cid(name: &str) -> Cid {
+        Cid::new_v1(Codec::DagCBOR, Blake2b256::digest(name.as_bytes()))
+    }
+
+    /// Add the tipset `name` at `height` on top of the tipset `parent`, returning it. The
+    /// tipset becomes the head if it's higher, a lower height is a null round.
+    pub fn add(&self, name: &str, height: ChainEpoch, parent: Option<&str>) -> TipSet {
+        let state_root = json!({"/": "bafy2bzacec43cadndvrgpiq3lia65pbyj2t32jltlqp2oszlvknwn5wx3vyii"});
+        let tipset: TipSet = serde_json::from_value(json!({
+            "Cids": [CidJson(Self::cid(name))],
+            "Blocks": [{
+                "Miner": "t01000", "Ticket": {"VRFProof": ""}, "ElectionProof": {"VRFProof": ""},
+                "BeaconEntries": null, "WinPoStProof": [],
+                "Parents": parent.map(|parent| vec![CidJson(Self::cid(parent))]).unwrap_or_default(),
+                "ParentWeight": "0", "Height": height,
+                "ParentStateRoot": state_root, "ParentMessageReceipts": state_root, "Messages": state_root,
+                "BLSAggregate": {"Type": 2, "Data": ""}, "Timestamp": 0, "BlockSig": {"Type": 2, "Data": ""},
+                "ForkSignaling": 0,
+            }],
+            "Height": height,
+        }))
+        .expect("the block of a tipset is valid");
+
+        let mut state = self.state.lock().expect("lock poisoned");
+        let higher = match &state.head {
+            Some(head) => state.tipsets[head].height < height,
+            None => true,
+        };
+        if higher {
+            state.head = Some(Self::cid(name));
+        }
+        state.tipsets.insert(Self::cid(name), tipset.clone());
+        tipset
+    }
+
+    /// Add a tipset on top of the head, one epoch higher, named by its height.
+    pub fn grow(&self) -> TipSet {
+        let head = self.head();
+        self.add(&(head.height + 1).to_string(), head.height + 1, Some(&head.height.to_string()))
+    }
+
+    /// The tipset named `name`, which must have been added.
+    pub fn get(&self, name: &str) -> TipSet {
+        self.tipset(Self::cid(name))
+    }
+
+    /// The highest tipset.
+    pub fn head(&self) -> TipSet {
+        let state = self.state.lock().expect("lock poisoned");
+        state.tipsets[state.head.as_ref().expect("the chain has tipsets")].clone()
+    }
+
+    fn tipset(&self, cid: Cid) -> TipSet {
+        self.state.lock().expect("lock poisoned").tipsets[&cid].clone()
+    }
+
+    fn parent(&self, tipset: &TipSet) -> TipSet {
+        self.tipset(tipset.blocks[0].parents[0].clone())
+    }
+
+    // Like Lotus, the tipset below a null round.
+    fn tipset_by_height(&self, height: ChainEpoch, from: Cid) -> TipSet {
+        let mut tipset = self.tipset(from);
+        while tipset.height > height {
+            tipset = self.parent(&tipset);
+        }
+        tipset
+    }
+
+    // The reverts from `from` down to the common ancestor, then the applies up to `to`.
+    fn path(&self, from: Cid, to: Cid) -> Value {
+        let (mut from, mut to) = (self.tipset(from), self.tipset(to));
+        let (mut reverts, mut applies) = (vec![], vec![]);
+        while from.cids != to.cids {
+            if from.height >= to.height {
+                let parent = self.parent(&from);
+                reverts.push(json!({"Type": "revert", "Val": from}));
+                from = parent;
+            } else {
+                let parent = self.parent(&to);
+                applies.push(json!({"Type": "apply", "Val": to}));
+                to = parent;
+            }
+        }
+        applies.reverse();
+        reverts.extend(applies);
+        Value::Array(reverts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::runtime::Runtime;
+    use crate::Http;
+    use crate::transports::Transport;
+
+    #[test]
+    fn handlers_and_fixtures() {
+        let mut rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let server = MockServer::start();
+            server
+                .fixture("Filecoin.Version", json!({"Version": "0.4.0", "APIVersion": 1, "BlockDelay": 25}))
+                .handle("LogList", |params| Ok(json!([params.len()])))
+                .fail("Shutdown", RpcError::internal_error());
+            let http = Http::new(&server.url());
+
+            let version: Value = http.send("Filecoin.Version", Params::Array(vec![])).await.unwrap();
+            assert_eq!(version["APIVersion"], 1);
+            let logs: Value = http.send("Filecoin.LogList", Params::Array(vec![json!(1)])).await.unwrap();
+            assert_eq!(logs, json!([1]));
+            let shutdown = http.send::<_, Value>("Filecoin.Shutdown", Params::Array(vec![])).await;
+            assert_eq!(shutdown.unwrap_err(), Error::Rpc(RpcError::internal_error()));
+            let unknown = http.send::<_, Value>("Filecoin.ChainHead", Params::Array(vec![])).await;
+            assert_eq!(unknown.unwrap_err(), Error::Rpc(RpcError::method_not_found()));
+
+            let results = http
+                .send_batch(vec![("Filecoin.LogList", Params::Array(vec![])), ("Filecoin.ChainHead", Params::None)])
+                .await
+                .unwrap();
+            assert_eq!(results[0], Ok(json!([0])));
+            assert!(results[1].is_err());
+
+            let calls = server.take_calls();
+            assert_eq!(calls.len(), 6);
+            assert_eq!(calls[1], RecordedCall { method: "Filecoin.LogList".into(), params: vec![json!(1)] });
+            assert!(server.calls().is_empty());
+        });
+    }
+
+    #[test]
+    fn reentrant_handler() {
+        let mut rt = Runtime::new().unwrap();
+        let server = Arc::new(rt.enter(MockServer::start));
+        let handler_server = server.clone();
+        server.handle("ChainHead", move |_| {
+            handler_server.fixture("ChainGetGenesis", json!(null));
+            Ok(json!(handler_server.calls().len()))
+        });
+        let http = Http::new(&server.url());
+
+        let calls: Value = rt.block_on(http.send("Filecoin.ChainHead", Params::Array(vec![]))).unwrap();
+        assert_eq!(calls, json!(1));
+        let genesis: Value = rt.block_on(http.send("Filecoin.ChainGetGenesis", Params::Array(vec![]))).unwrap();
+        assert_eq!(genesis, Value::Null);
+    }
+}
@@ -0,0 +1,234 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use libp2p_core::Multiaddr;
+use libp2p_core::multiaddr::Protocol;
+use crate::error::{Error, Result};
+use crate::transports::{Http, HttpBuilder};
+
+/// Kind of Lotus node, which selects the environment variables and the default repo.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NodeType {
+    /// `lotus daemon`
+    FullNode,
+    /// `lotus-miner`
+    Miner,
+}
+
+impl NodeType {
+    // The first variable set is used, the later ones are deprecated names.
+    fn api_info_vars(self) -> &'static [&'static str] {
+        match self {
+            NodeType::FullNode => &["FULLNODE_API_INFO", "LOTUS_API_INFO"],
+            NodeType::Miner => &["MINER_API_INFO", "STORAGE_API_INFO"],
+        }
+    }
+
+    fn repo_vars(self) -> &'static [&'static str] {
+        match self {
+            NodeType::FullNode => &["LOTUS_PATH"],
+            NodeType::Miner => &["LOTUS_MINER_PATH", "LOTUS_STORAGE_PATH"],
+        }
+    }
+
+    fn default_repo(self) -> &'static str {
+        match self {
+            NodeType::FullNode => "~/.lotus",
+            NodeType::Miner => "~/.lotusminer",
+        }
+    }
+}
+
+/// Endpoint and token of a Lotus API, as written in the `api` and `token` files of the
+/// repo or in a `token:multiaddr` environment variable such as `FULLNODE_API_INFO`.
+#[derive(Clone, PartialEq)]
+pub struct ApiInfo {
+    pub addr: Multiaddr,
+    pub token: Option<String>,
+}
+
+impl ApiInfo {
+    /// Read the `api` file and the optional `token` file of the repo at `path`, `~` is expanded.
+    pub fn from_repo<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = expand_home(path.as_ref());
+        let addr = fs::read_to_string(path.join("api"))?;
+        let addr = addr.trim().parse().map_err(|e| Error::Config(format!("Invalid api multiaddr: {}", e)))?;
+        let token = match fs::read_to_string(path.join("token")) {
+            Ok(token) => Some(token.trim().to_string()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { addr, token })
+    }
+
+    /// Read the `*_API_INFO` environment variable of the node type, if set.
+    pub fn from_env(node: NodeType) -> Option<Result<Self>> {
+        node.api_info_vars()
+            .iter()
+            .find_map(|var| env::var(var).ok())
+            .map(|info| info.parse())
+    }
+
+    /// Find the API of the node type: from the `*_API_INFO` environment variable if set,
+    /// else from the repo given by `LOTUS_PATH`/`LOTUS_MINER_PATH` or the default repo.
+    pub fn discover(node: NodeType) -> Result<Self> {
+        if let Some(info) = Self::from_env(node) {
+            return info;
+        }
+        let repo = node.repo_vars()
+            .iter()
+            .find_map(|var| env::var(var).ok())
+            .unwrap_or_else(|| node.default_repo().into());
+        Self::from_repo(repo)
+    }
+
+    /// Url of the JSON-RPC endpoint, e.g. `http://127.0.0.1:1234/rpc/v0`.
+    pub fn url(&self) -> Result<String> {
+        let mut host = None;
+        let mut port = None;
+        let mut scheme = "http";
+        for protocol in self.addr.iter() {
+            match protocol {
+                Protocol::Ip4(ip) => host = Some(ip.to_string()),
+                Protocol::Ip6(ip) => host = Some(format!("[{}]", ip)),
+                Protocol::Dns4(name) | Protocol::Dns6(name) => host = Some(name.into_owned()),
+                Protocol::Tcp(tcp) => port = Some(tcp),
+                Protocol::Http => scheme = "http",
+                Protocol::Https => scheme = "https",
+                Protocol::Ws(_) => scheme = "ws",
+                Protocol::Wss(_) => scheme = "wss",
+                protocol => return Err(Error::Config(format!("Unsupported protocol in {}: {}", self.addr, protocol))),
+            }
+        }
+        match (host, port) {
+            (Some(host), Some(port)) => Ok(format!("{}://{}:{}/rpc/v0", scheme, host, port)),
+            _ => Err(Error::Config(format!("Missing host or port in {}", self.addr))),
+        }
+    }
+
+    /// Http transport to the API, authenticated with the token if any.
+    pub fn http(&self) -> Result<Http> {
+        self.http_builder()?.build()
+    }
+
+    /// Builder of the Http transport to the API, to configure more than the token.
+    pub fn http_builder(&self) -> Result<HttpBuilder> {
+        let url = self.url()?;
+        let builder = match url.strip_prefix("ws") {
+            Some(url) => Http::builder(&format!("http{}", url)),
+            None => Http::builder(&url),
+        };
+        Ok(match &self.token {
+            Some(token) => builder.bearer_auth(token.as_str()),
+            None => builder,
+        })
+    }
+}
+
+// The token is a secret.
+impl fmt::Debug for ApiInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiInfo")
+            .field("addr", &self.addr)
+            .field("token", &self.token.as_ref().map(|_| "<hidden>"))
+            .finish()
+    }
+}
+
+impl FromStr for ApiInfo {
+    type Err = Error;
+
+    /// Parse `token:multiaddr`, or a bare multiaddr.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        // A multiaddr starts with `/`, and may contain `:` in an IPv6 address.
+        let (token, addr) = match s.find(':') {
+            Some(i) if !s.starts_with('/') => (Some(s[..i].to_string()), &s[i + 1..]),
+            _ => (None, s),
+        };
+        let addr = addr.parse().map_err(|e| Error::Config(format!("Invalid api multiaddr {}: {}", addr, e)))?;
+        Ok(Self { addr, token })
+    }
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), env::var_os("HOME")) {
+        (Ok(path), Some(home)) => PathBuf::from(home).join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_info_parse() {
+        let info: ApiInfo = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.e30.sig:/ip4/127.0.0.1/tcp/1234/http".parse().unwrap();
+        assert_eq!(info.token.as_deref(), Some("eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.e30.sig"));
+        assert_eq!(info.url().unwrap(), "http://127.0.0.1:1234/rpc/v0");
+        assert!(!format!("{:?}", info).contains("e30"));
+
+        let info: ApiInfo = "/dns4/lotus.local/tcp/2345/wss".parse().unwrap();
+        assert_eq!(info.token, None);
+        assert_eq!(info.url().unwrap(), "wss://lotus.local:2345/rpc/v0");
+
+        let info: ApiInfo = "token:/ip6/::1/tcp/1234".parse().unwrap();
+        assert_eq!(info.url().unwrap(), "http://[::1]:1234/rpc/v0");
+        let info: ApiInfo = "/ip6/::1/tcp/1234/http".parse().unwrap();
+        assert_eq!(info.token, None);
+        assert_eq!(info.url().unwrap(), "http://[::1]:1234/rpc/v0");
+
+        assert!("token:/ip4/127.0.0.1/udp/1234".parse::<ApiInfo>().unwrap().url().is_err());
+        assert!("token:/ip4/127.0.0.1".parse::<ApiInfo>().unwrap().url().is_err());
+        assert!("token:not-a-multiaddr".parse::<ApiInfo>().is_err());
+    }
+
+    #[test]
+    fn api_info_from_repo() {
+        let repo = env::temp_dir().join(format!("lotus-api-repo-{}", std::process::id()));
+        fs::create_dir_all(&repo).unwrap();
+        fs::write(repo.join("api"), "/ip4/127.0.0.1/tcp/1234/http").unwrap();
+
+        let info = ApiInfo::from_repo(&repo).unwrap();
+        assert_eq!(info.token, None);
+        assert_eq!(info.url().unwrap(), "http://127.0.0.1:1234/rpc/v0");
+
+        fs::write(repo.join("token"), "secret\n").unwrap();
+        let info = ApiInfo::from_repo(&repo).unwrap();
+        assert_eq!(info.token.as_deref(), Some("secret"));
+
+        fs::remove_dir_all(&repo).unwrap();
+        assert!(ApiInfo::from_repo(&repo).is_err());
+    }
+
+    // The only test using the variables of the miner, which are global to the process.
+    #[test]
+    fn api_info_from_env() {
+        let repo = env::temp_dir().join(format!("lotus-api-miner-repo-{}", std::process::id()));
+        fs::create_dir_all(&repo).unwrap();
+        fs::write(repo.join("api"), "/ip4/127.0.0.1/tcp/2345/http").unwrap();
+        env::set_var("LOTUS_MINER_PATH", &repo);
+
+        env::set_var("MINER_API_INFO", "secret:/ip4/10.0.0.1/tcp/2345/http");
+        let info = ApiInfo::discover(NodeType::Miner).unwrap();
+        assert_eq!(info.token.as_deref(), Some("secret"));
+        assert_eq!(info.url().unwrap(), "http://10.0.0.1:2345/rpc/v0");
+        env::remove_var("MINER_API_INFO");
+
+        // The deprecated name is still read.
+        env::set_var("STORAGE_API_INFO", "/ip4/10.0.0.2/tcp/2345/http");
+        assert_eq!(ApiInfo::from_env(NodeType::Miner).unwrap().unwrap().url().unwrap(), "http://10.0.0.2:2345/rpc/v0");
+        env::set_var("STORAGE_API_INFO", "secret:not-a-multiaddr");
+        assert!(ApiInfo::discover(NodeType::Miner).is_err());
+        env::remove_var("STORAGE_API_INFO");
+
+        assert!(ApiInfo::from_env(NodeType::Miner).is_none());
+        let info = ApiInfo::discover(NodeType::Miner).unwrap();
+        assert_eq!(info.url().unwrap(), "http://127.0.0.1:2345/rpc/v0");
+        env::remove_var("LOTUS_MINER_PATH");
+        fs::remove_dir_all(&repo).unwrap();
+    }
+}
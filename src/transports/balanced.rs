@@ -0,0 +1,409 @@
+use std::collections::HashSet;
+use std::sync::{Mutex, atomic::{AtomicUsize, Ordering}};
+use std::time::{Duration, Instant};
+use jsonrpc_core::{Request, Response, Params, Call, Version, MethodCall, Id};
+use log::{debug, warn};
+use serde_json::Value;
+use crate::error::{Error, Result};
+use crate::transports::{Transport, RequestId, IDEMPOTENT_METHODS};
+
+// Weight of the last call in the moving average of the latency.
+const LATENCY_WEIGHT: f64 = 0.2;
+
+/// How `Balanced` chooses the endpoint of a read call.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Policy {
+    /// Each healthy endpoint in turn.
+    RoundRobin,
+    /// The healthy endpoint with the lowest average latency.
+    LeastLatency,
+    /// The first healthy endpoint, in the given order.
+    PrimaryFallback,
+}
+
+#[derive(Clone, Debug, Default)]
+struct EndpointState {
+    /// Lagging behind, or failing, at the last health check.
+    unhealthy: bool,
+    /// Time of the last transport error of a call.
+    failed_at: Option<Instant>,
+    latency: Option<Duration>,
+    height: Option<i64>,
+}
+
+/// Health of one endpoint, as seen by the last calls and health check.
+#[derive(Clone, Debug)]
+pub struct EndpointHealth {
+    pub healthy: bool,
+    /// Average latency of the calls.
+    pub latency: Option<Duration>,
+    /// Height of the head at the last health check.
+    pub height: Option<i64>,
+}
+
+/// Transport spreading the calls over several endpoints, e.g. several `Http` to
+/// different Lotus nodes.
+///
+/// Read calls (see `IDEMPOTENT_METHODS`) are sent to an endpoint chosen by the policy,
+/// and sent again to the next one if it fails with a transport error. The other calls,
+/// such as `MpoolPush`, all go to the same endpoint while it is healthy, so that the
+/// nonces assigned by its message pool stay consistent.
+///
+/// An endpoint is unhealthy for `retry_after` after a transport error, or until the next
+/// `health_check` when it finds its head more than `max_lag` epochs behind the highest
+/// one. Unhealthy endpoints are only used when no endpoint is healthy.
+pub struct Balanced<T> {
+    id: AtomicUsize,
+    endpoints: Vec<(T, Mutex<EndpointState>)>,
+    policy: Policy,
+    next: AtomicUsize,
+    sticky: Mutex<Option<usize>>,
+    max_lag: i64,
+    retry_after: Duration,
+    idempotent: HashSet<String>,
+}
+
+impl<T: Transport + Send + Sync> Balanced<T> {
+    /// Balance over `endpoints`, failing with `Error::Config` when there is none.
+    pub fn new(endpoints: Vec<T>, policy: Policy) -> Result<Self> {
+        if endpoints.is_empty() {
+            return Err(Error::Config("Balanced needs at least one endpoint".into()));
+        }
+        Ok(Self {
+            id: Default::default(),
+            endpoints: endpoints.into_iter().map(|endpoint| (endpoint, Default::default())).collect(),
+            policy,
+            next: Default::default(),
+            sticky: Default::default(),
+            max_lag: 5,
+            retry_after: Duration::from_secs(30),
+            idempotent: IDEMPOTENT_METHODS.iter().map(|method| method.to_string()).collect(),
+        })
+    }
+
+    /// Epochs an endpoint may lag behind the highest head and stay healthy, 5 by default.
+    pub fn max_lag(mut self, max_lag: i64) -> Self {
+        self.max_lag = max_lag;
+        self
+    }
+
+    /// Time an endpoint stays unhealthy after a transport error, 30 seconds by default.
+    /// The next call sent to it then checks whether it's back.
+    pub fn retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// Also balance `method`, e.g. `Filecoin.StateMinerDeadlines`.
+    pub fn idempotent<M: Into<String>>(mut self, method: M) -> Self {
+        self.idempotent.insert(method.into());
+        self
+    }
+
+    pub fn endpoints(&self) -> impl Iterator<Item = &T> {
+        self.endpoints.iter().map(|(endpoint, _)| endpoint)
+    }
+
+    /// Health of the endpoints, in order.
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        self.endpoints
+            .iter()
+            .map(|(_, state)| {
+                let state = state.lock().expect("lock poisoned");
+                EndpointHealth {
+                    healthy: state.is_healthy(self.retry_after),
+                    latency: state.latency,
+                    height: state.height,
+                }
+            })
+            .collect()
+    }
+
+    /// Query the head of every endpoint, marking unhealthy the ones which fail or lag
+    /// behind. Should be called periodically, e.g. every block time.
+    pub async fn health_check(&self) {
+        let heads = futures::future::join_all(self.endpoints.iter().map(|(endpoint, _)| async move {
+            let start = Instant::now();
+            let head = endpoint.send::<_, Value>("Filecoin.ChainHead", Params::Array(vec![])).await;
+            (start.elapsed(), head.map(|head| head["Height"].as_i64()))
+        }))
+        .await;
+        let highest = heads
+            .iter()
+            .filter_map(|(_, head)| head.as_ref().ok().copied().flatten())
+            .max();
+
+        for (i, ((_, state), (latency, head))) in self.endpoints.iter().zip(heads).enumerate() {
+            let mut state = state.lock().expect("lock poisoned");
+            match head {
+                Ok(height) => {
+                    state.record_latency(latency);
+                    state.failed_at = None;
+                    state.height = height;
+                    state.unhealthy = match (height, highest) {
+                        (Some(height), Some(highest)) => highest - height > self.max_lag,
+                        _ => true,
+                    };
+                    if state.unhealthy {
+                        debug!("Endpoint {} lags behind: {:?} < {:?}", i, height, highest);
+                    }
+                }
+                Err(e) => {
+                    debug!("Health check of endpoint {} failed: {}", i, e);
+                    state.unhealthy = true;
+                    state.failed_at = Some(Instant::now());
+                }
+            }
+        }
+    }
+
+    fn is_idempotent(&self, request: &Request) -> bool {
+        let idempotent = |call: &Call| match call {
+            Call::MethodCall(MethodCall { method, .. }) => self.idempotent.contains(method),
+            _ => false,
+        };
+        match request {
+            Request::Single(call) => idempotent(call),
+            Request::Batch(calls) => calls.iter().all(idempotent),
+        }
+    }
+
+    fn is_healthy(&self, i: usize) -> bool {
+        self.endpoints[i].1.lock().expect("lock poisoned").is_healthy(self.retry_after)
+    }
+
+    // Endpoints to try in order, the healthy ones first.
+    fn candidates(&self) -> Vec<usize> {
+        let count = self.endpoints.len();
+        let mut candidates = match self.policy {
+            Policy::RoundRobin => {
+                let start = self.next.fetch_add(1, Ordering::AcqRel);
+                (0..count).map(|i| (start + i) % count).collect()
+            }
+            Policy::LeastLatency => {
+                let mut candidates = (0..count).collect::<Vec<_>>();
+                // Endpoints without latency yet come first, to measure them.
+                candidates.sort_by_key(|&i| self.endpoints[i].1.lock().expect("lock poisoned").latency);
+                candidates
+            }
+            Policy::PrimaryFallback => (0..count).collect(),
+        };
+        // Stable, so the order of the policy is kept among healthy endpoints.
+        candidates.sort_by_key(|&i| !self.is_healthy(i));
+        candidates
+    }
+
+    // The endpoint of the calls which aren't idempotent, kept while it is healthy.
+    fn sticky(&self) -> usize {
+        let mut sticky = self.sticky.lock().expect("lock poisoned");
+        match *sticky {
+            Some(i) if self.is_healthy(i) => i,
+            previous => {
+                let i = self.candidates()[0];
+                if previous.is_some() {
+                    warn!("Write endpoint switched to {}", i);
+                }
+                *sticky = Some(i);
+                i
+            }
+        }
+    }
+
+    async fn execute_on(&self, i: usize, id: RequestId, request: &Request) -> Result<Response> {
+        let (endpoint, state) = &self.endpoints[i];
+        let start = Instant::now();
+        let result = endpoint.execute(id, request).await;
+        let mut state = state.lock().expect("lock poisoned");
+        match &result {
+            Err(e) if e.is_retryable() => state.failed_at = Some(Instant::now()),
+            _ => {
+                state.record_latency(start.elapsed());
+                state.failed_at = None;
+            }
+        }
+        result
+    }
+}
+
+impl EndpointState {
+    fn is_healthy(&self, retry_after: Duration) -> bool {
+        !self.unhealthy && !matches!(self.failed_at, Some(failed_at) if failed_at.elapsed() < retry_after)
+    }
+
+    fn record_latency(&mut self, latency: Duration) {
+        self.latency = Some(match self.latency {
+            Some(average) => average.mul_f64(1.0 - LATENCY_WEIGHT) + latency.mul_f64(LATENCY_WEIGHT),
+            None => latency,
+        });
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport + Send + Sync> Transport for Balanced<T> {
+    fn prepare<M: Into<String>>(&self, method: M, params: Params) -> (RequestId, Call) {
+        let id = self.id.fetch_add(1, Ordering::AcqRel);
+        let call = Call::MethodCall(MethodCall {
+            jsonrpc: Some(Version::V2),
+            id: Id::Num(id as u64),
+            method: method.into(),
+            params,
+        });
+        (id, call)
+    }
+
+    async fn execute(&self, id: RequestId, request: &Request) -> Result<Response> {
+        if !self.is_idempotent(request) {
+            return self.execute_on(self.sticky(), id, request).await;
+        }
+
+        let mut last_error = Error::Unreachable;
+        for i in self.candidates() {
+            match self.execute_on(i, id, request).await {
+                Err(e) if e.is_retryable() => {
+                    debug!("Endpoint {} failed: {}, trying the next one", i, e);
+                    last_error = e;
+                }
+                result => return result,
+            }
+        }
+        Err(last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use jsonrpc_core::{Output, Success};
+    use serde_json::json;
+    use tokio::runtime::Runtime;
+
+    // Answers `ChainHead` with its height, and the other calls with its index.
+    struct Node {
+        index: usize,
+        height: i64,
+        down: AtomicBool,
+    }
+
+    impl Node {
+        fn new(index: usize, height: i64) -> Self {
+            Self { index, height, down: AtomicBool::new(false) }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Transport for Node {
+        fn prepare<M: Into<String>>(&self, method: M, params: Params) -> (RequestId, Call) {
+            let call = Call::MethodCall(MethodCall {
+                jsonrpc: Some(Version::V2),
+                id: Id::Num(0),
+                method: method.into(),
+                params,
+            });
+            (0, call)
+        }
+
+        async fn execute(&self, id: RequestId, request: &Request) -> Result<Response> {
+            if self.down.load(Ordering::Acquire) {
+                return Err(Error::Transport("connection refused".into()));
+            }
+            let result = match request {
+                Request::Single(Call::MethodCall(call)) if call.method == "Filecoin.ChainHead" => {
+                    json!({"Height": self.height})
+                }
+                _ => json!(self.index),
+            };
+            Ok(Response::Single(Output::Success(Success {
+                jsonrpc: Some(Version::V2),
+                result,
+                id: Id::Num(id as u64),
+            })))
+        }
+    }
+
+    fn call(balanced: &Balanced<Node>, rt: &mut Runtime, method: &str) -> Result<usize> {
+        rt.block_on(balanced.send(method, Params::Array(vec![])))
+    }
+
+    #[test]
+    fn no_endpoint() {
+        let balanced = Balanced::<Node>::new(vec![], Policy::RoundRobin);
+        assert_eq!(balanced.err(), Some(Error::Config("Balanced needs at least one endpoint".into())));
+    }
+
+    #[test]
+    fn round_robin_and_sticky_writes() {
+        let mut rt = Runtime::new().unwrap();
+        let nodes = vec![Node::new(0, 100), Node::new(1, 100), Node::new(2, 100)];
+        let balanced = Balanced::new(nodes, Policy::RoundRobin).unwrap();
+
+        let reads = (0..6).map(|_| call(&balanced, &mut rt, "Filecoin.WalletBalance").unwrap()).collect::<Vec<_>>();
+        assert_eq!(reads, vec![0, 1, 2, 0, 1, 2]);
+        let write = call(&balanced, &mut rt, "Filecoin.MpoolPushMessage").unwrap();
+        for _ in 0..3 {
+            assert_eq!(call(&balanced, &mut rt, "Filecoin.MpoolPush").unwrap(), write);
+        }
+
+        // The write endpoint fails: the write fails, the next ones switch endpoint.
+        balanced.endpoints[write].0.down.store(true, Ordering::Release);
+        assert!(call(&balanced, &mut rt, "Filecoin.MpoolPush").is_err());
+        let switched = call(&balanced, &mut rt, "Filecoin.MpoolPush").unwrap();
+        assert_ne!(switched, write);
+        assert_eq!(call(&balanced, &mut rt, "Filecoin.MpoolPush").unwrap(), switched);
+
+        // Reads fail over, and skip the unhealthy endpoint.
+        for _ in 0..4 {
+            assert_ne!(call(&balanced, &mut rt, "Filecoin.ChainGetBlock").unwrap(), write);
+        }
+    }
+
+    #[test]
+    fn health_check_lag() {
+        let mut rt = Runtime::new().unwrap();
+        let nodes = vec![Node::new(0, 90), Node::new(1, 100), Node::new(2, 98)];
+        let balanced = Balanced::new(nodes, Policy::PrimaryFallback).unwrap().max_lag(3);
+
+        rt.block_on(balanced.health_check());
+        let health = balanced.health();
+        assert!(!health[0].healthy);
+        assert!(health[1].healthy && health[2].healthy);
+        assert_eq!(health[0].height, Some(90));
+        assert_eq!(call(&balanced, &mut rt, "Filecoin.StateGetActor").unwrap(), 1);
+
+        balanced.endpoints[1].0.down.store(true, Ordering::Release);
+        rt.block_on(balanced.health_check());
+        assert_eq!(call(&balanced, &mut rt, "Filecoin.StateGetActor").unwrap(), 2);
+
+        // When no endpoint is healthy, the unhealthy ones are still tried.
+        balanced.endpoints[2].0.down.store(true, Ordering::Release);
+        assert_eq!(call(&balanced, &mut rt, "Filecoin.StateGetActor").unwrap(), 0);
+    }
+
+    #[test]
+    fn least_latency() {
+        let mut rt = Runtime::new().unwrap();
+        let balanced = Balanced::new(vec![Node::new(0, 100), Node::new(1, 100)], Policy::LeastLatency).unwrap();
+        balanced.endpoints[0].1.lock().unwrap().latency = Some(Duration::from_millis(80));
+        balanced.endpoints[1].1.lock().unwrap().latency = Some(Duration::from_millis(20));
+        assert_eq!(call(&balanced, &mut rt, "Filecoin.ChainGetTipSet").unwrap(), 1);
+    }
+
+    #[test]
+    fn failed_endpoint_recovers() {
+        let mut rt = Runtime::new().unwrap();
+        let balanced = Balanced::new(vec![Node::new(0, 100), Node::new(1, 100)], Policy::PrimaryFallback).unwrap();
+
+        balanced.endpoints[0].0.down.store(true, Ordering::Release);
+        assert_eq!(call(&balanced, &mut rt, "Filecoin.ChainGetBlock").unwrap(), 1);
+        balanced.endpoints[0].0.down.store(false, Ordering::Release);
+        assert_eq!(call(&balanced, &mut rt, "Filecoin.ChainGetBlock").unwrap(), 1);
+        assert!(!balanced.health()[0].healthy);
+
+        // Once `retry_after` has elapsed, the endpoint is tried again.
+        let mut state = balanced.endpoints[0].1.lock().unwrap();
+        state.failed_at = state.failed_at.map(|failed_at| failed_at - balanced.retry_after);
+        drop(state);
+        assert_eq!(call(&balanced, &mut rt, "Filecoin.ChainGetBlock").unwrap(), 0);
+        assert!(balanced.health()[0].healthy);
+    }
+}
@@ -0,0 +1,259 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}};
+use jsonrpc_core::{Request, Response, Output, Params, Call, Version, MethodCall, Id};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::error::{Error, Result};
+use crate::transports::{Transport, RequestId};
+
+/// One line of a cassette.
+#[derive(Serialize, Deserialize)]
+struct Interaction {
+    request: Request,
+    response: Response,
+}
+
+/// Transport recording every request/response pair of the inner transport to a
+/// JSON-lines cassette, which can be served again by `Replay`.
+pub struct Record<T> {
+    inner: T,
+    cassette: Mutex<File>,
+}
+
+impl<T: Transport> Record<T> {
+    /// Record the traffic of `inner` to the cassette at `path`, replacing its content.
+    pub fn new<P: AsRef<Path>>(inner: T, path: P) -> Result<Self> {
+        Ok(Self {
+            inner,
+            cassette: Mutex::new(File::create(path)?),
+        })
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport + Send + Sync> Transport for Record<T> {
+    fn prepare<M: Into<String>>(&self, method: M, params: Params) -> (RequestId, Call) {
+        self.inner.prepare(method, params)
+    }
+
+    async fn execute(&self, id: RequestId, request: &Request) -> Result<Response> {
+        let response = self.inner.execute(id, request).await?;
+        let line = serde_json::to_string(&Interaction {
+            request: request.clone(),
+            response: response.clone(),
+        })?;
+        let mut cassette = self.cassette.lock().expect("lock poisoned");
+        writeln!(cassette, "{}", line)?;
+        Ok(response)
+    }
+}
+
+/// Transport serving the responses of a cassette written by `Record`, without network.
+///
+/// Requests are matched by method and params, ignoring the ids. The responses recorded
+/// for the same request are served in order, the last one being repeated. A request
+/// missing from the cassette fails with `Error::Protocol`, which isn't retried.
+pub struct Replay {
+    id: Arc<AtomicUsize>,
+    interactions: Mutex<HashMap<String, VecDeque<Interaction>>>,
+}
+
+impl Replay {
+    /// Load the cassette at `path`.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut interactions = HashMap::<_, VecDeque<_>>::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let interaction: Interaction = serde_json::from_str(&line)?;
+            interactions
+                .entry(request_key(&interaction.request))
+                .or_default()
+                .push_back(interaction);
+        }
+
+        Ok(Self {
+            id: Default::default(),
+            interactions: Mutex::new(interactions),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for Replay {
+    fn prepare<M: Into<String>>(&self, method: M, params: Params) -> (RequestId, Call) {
+        let id = self.id.fetch_add(1, Ordering::AcqRel);
+        let call = Call::MethodCall(MethodCall {
+            jsonrpc: Some(Version::V2),
+            id: Id::Num(id as u64),
+            method: method.into(),
+            params,
+        });
+        (id, call)
+    }
+
+    async fn execute(&self, _id: RequestId, request: &Request) -> Result<Response> {
+        let key = request_key(request);
+        let mut interactions = self.interactions.lock().expect("lock poisoned");
+        let recorded = match interactions.get_mut(&key) {
+            Some(recorded) if recorded.len() > 1 => recorded.pop_front().expect("len > 1; qed"),
+            Some(recorded) if !recorded.is_empty() => Interaction {
+                request: recorded[0].request.clone(),
+                response: recorded[0].response.clone(),
+            },
+            _ => return Err(Error::Protocol(format!("No recorded response for {}", key))),
+        };
+
+        // Map the recorded ids to the ids of the request, call by call.
+        let ids = call_ids(&recorded.request)
+            .into_iter()
+            .zip(call_ids(request))
+            .collect::<HashMap<_, _>>();
+        let with_id = |output: Output| match output {
+            Output::Success(mut success) => {
+                success.id = ids.get(&success.id).cloned().unwrap_or(success.id);
+                Output::Success(success)
+            }
+            Output::Failure(mut failure) => {
+                failure.id = ids.get(&failure.id).cloned().unwrap_or(failure.id);
+                Output::Failure(failure)
+            }
+        };
+        Ok(match recorded.response {
+            Response::Single(output) => Response::Single(with_id(output)),
+            Response::Batch(outputs) => Response::Batch(outputs.into_iter().map(with_id).collect()),
+        })
+    }
+}
+
+fn call_ids(request: &Request) -> Vec<Id> {
+    let id = |call: &Call| match call {
+        Call::MethodCall(call) => call.id.clone(),
+        Call::Notification(_) => Id::Null,
+        Call::Invalid { id } => id.clone(),
+    };
+    match request {
+        Request::Single(call) => vec![id(call)],
+        Request::Batch(calls) => calls.iter().map(id).collect(),
+    }
+}
+
+// The request without its ids, e.g. `{"method":"Filecoin.ChainHead","params":[]}`.
+fn request_key(request: &Request) -> String {
+    let strip = |call: &Call| {
+        let mut call = serde_json::to_value(call).expect("Serialize `Call` never fails");
+        if let Value::Object(call) = &mut call {
+            call.remove("id");
+            call.remove("jsonrpc");
+        }
+        call
+    };
+    let key = match request {
+        Request::Single(call) => strip(call),
+        Request::Batch(calls) => Value::Array(calls.iter().map(strip).collect()),
+    };
+    key.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::runtime::Runtime;
+    use crate::Http;
+    use crate::testing::MockServer;
+    use crate::transports::{Retry, RetryConfig};
+
+    #[test]
+    fn record_and_replay() {
+        let path = std::env::temp_dir().join(format!("lotus-api-cassette-{}.jsonl", std::process::id()));
+        let mut rt = Runtime::new().unwrap();
+        let server = rt.enter(MockServer::start);
+        let height = std::sync::atomic::AtomicU64::new(10);
+        server
+            .handle("ChainHeight", move |_| Ok(json!(height.fetch_add(1, Ordering::AcqRel))))
+            .handle("Echo", |params| Ok(Value::from(params)));
+
+        let record = Record::new(Http::new(&server.url()), &path).unwrap();
+        rt.block_on(async {
+            let _: u64 = record.send("Filecoin.ChainHeight", Params::Array(vec![])).await.unwrap();
+            let _: u64 = record.send("Filecoin.ChainHeight", Params::Array(vec![])).await.unwrap();
+            let _: Value = record.send("Filecoin.Echo", Params::Array(vec![json!(1)])).await.unwrap();
+            let results = record
+                .send_batch(vec![
+                    ("Filecoin.Echo", Params::Array(vec![json!(2)])),
+                    ("Filecoin.Missing", Params::Array(vec![])),
+                ])
+                .await
+                .unwrap();
+            assert_eq!(results.len(), 2);
+        });
+        drop(record);
+        drop(server);
+
+        let replay = Replay::new(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        rt.block_on(async {
+            // The ids differ from the recorded ones.
+            let results = replay
+                .send_batch(vec![
+                    ("Filecoin.Echo", Params::Array(vec![json!(2)])),
+                    ("Filecoin.Missing", Params::Array(vec![])),
+                ])
+                .await
+                .unwrap();
+            assert_eq!(results[0], Ok(json!([2])));
+            assert!(results[1].is_err());
+
+            let echo: Value = replay.send("Filecoin.Echo", Params::Array(vec![json!(1)])).await.unwrap();
+            assert_eq!(echo, json!([1]));
+            for expected in &[10, 11, 11] {
+                let height: u64 = replay.send("Filecoin.ChainHeight", Params::Array(vec![])).await.unwrap();
+                assert_eq!(height, *expected);
+            }
+
+            let missing = replay.send::<_, Value>("Filecoin.Echo", Params::Array(vec![json!(3)])).await;
+            assert!(missing.is_err());
+        });
+    }
+
+    #[test]
+    fn replay_miss_is_not_retried() {
+        let path = std::env::temp_dir().join(format!("lotus-api-cassette-miss-{}.jsonl", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"request":{"jsonrpc":"2.0","method":"Filecoin.ChainHeight","params":[],"id":0},"response":{"jsonrpc":"2.0","result":10,"id":0}}"#,
+        )
+        .unwrap();
+        let replay = Replay::new(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // A single retryable failure would open the breaker, failing the retry as unreachable.
+        let config = RetryConfig {
+            failure_threshold: 1,
+            ..Default::default()
+        };
+        let retry = Retry::with_config(replay, config);
+        let mut rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let missing = retry.send::<_, Value>("Filecoin.ChainHead", Params::Array(vec![])).await;
+            assert_eq!(
+                missing,
+                Err(Error::Protocol(
+                    r#"No recorded response for {"method":"Filecoin.ChainHead","params":[]}"#.into()
+                ))
+            );
+            let height: u64 = retry.send("Filecoin.ChainHeight", Params::Array(vec![])).await.unwrap();
+            assert_eq!(height, 10);
+        });
+    }
+}
@@ -1,8 +1,15 @@
+use std::collections::HashMap;
 use std::time::Duration;
 use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
 use jsonrpc_core::{Request, Response, Params, Call, Version, MethodCall, Id};
-use crate::error::Result;
-use crate::transports::{Transport, RequestId};
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::path::Path;
+use crate::error::{Error, Result};
+use crate::transports::{Transport, RequestId, ApiInfo, NodeType};
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Clone)]
 pub struct Http {
@@ -10,21 +17,63 @@ pub struct Http {
     url: String,
     bearer_auth: Option<String>,
     client: reqwest::Client,
+    // timeouts of the methods which differ from the client timeout
+    method_timeouts: Arc<HashMap<String, Duration>>,
+    timeout: Option<Duration>,
 }
 
 impl Http {
+    /// Client with the default configuration of `HttpBuilder`.
+    ///
+    /// Panics if the TLS backend can't be initialized, use `Http::builder` to handle it.
     pub fn new(url: &str) -> Self {
-        let client = reqwest::Client::builder()
-            .connect_timeout(Duration::from_secs(10))
-            .timeout(Duration::from_secs(30))
+        Self::builder(url)
             .build()
-            .expect("ClientBuilder config is valid; qed");
+            .expect("HttpBuilder default config is valid; qed")
+    }
+
+    pub fn builder(url: &str) -> HttpBuilder {
+        HttpBuilder::new(url)
+    }
+
+    /// Connect to the API of the Lotus repo at `path`, e.g. `~/.lotus`, with its token.
+    pub fn from_repo<P: AsRef<Path>>(path: P) -> Result<Self> {
+        ApiInfo::from_repo(path)?.http()
+    }
+
+    /// Connect to the API of the node found by `ApiInfo::discover`.
+    pub fn discover(node: NodeType) -> Result<Self> {
+        ApiInfo::discover(node)?.http()
+    }
+
+    /// Send `token` as bearer auth with every request, required by the methods
+    /// which need more than the read permission.
+    pub fn with_bearer_auth<T: Into<String>>(mut self, token: T) -> Self {
+        self.bearer_auth = Some(token.into());
+        self
+    }
 
+    /// Handle sharing the connections of this client, whose calls time out after `timeout`,
+    /// e.g. `http.with_timeout(Duration::from_secs(600)).chain_read_obj(&cid)`.
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
         Self {
-            id: Default::default(),
-            url: url.into(),
-            bearer_auth: None,
-            client,
+            timeout: Some(timeout),
+            ..self.clone()
+        }
+    }
+
+    // The longest timeout of the methods of the request, if any differs from the client one.
+    fn request_timeout(&self, request: &Request) -> Option<Duration> {
+        if self.timeout.is_some() {
+            return self.timeout;
+        }
+        let method_timeout = |call: &Call| match call {
+            Call::MethodCall(MethodCall { method, .. }) => self.method_timeouts.get(method).copied(),
+            _ => None,
+        };
+        match request {
+            Request::Single(call) => method_timeout(call),
+            Request::Batch(calls) => calls.iter().filter_map(method_timeout).max(),
         }
     }
 
@@ -35,7 +84,202 @@ impl Http {
         } else {
             builder
         };
-        Ok(builder.send().await?.json().await?)
+        let builder = if let Some(timeout) = self.request_timeout(request) {
+            builder.timeout(timeout)
+        } else {
+            builder
+        };
+        let response = builder.send().await?;
+        match response.status() {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                Err(Error::PermissionDenied(response.text().await.unwrap_or_default()))
+            }
+            status => {
+                // The node may answer a JSON-RPC error with an error status; only a
+                // server error may go away on retry.
+                let body = response.bytes().await?;
+                match serde_json::from_slice(&body) {
+                    Ok(response) => Ok(response),
+                    Err(_) if status.is_server_error() => Err(Error::Transport(format!("HTTP status {}", status))),
+                    Err(_) if !status.is_success() => Err(Error::Protocol(format!("HTTP status {}", status))),
+                    Err(e) => Err(e.into()),
+                }
+            }
+        }
+    }
+}
+
+/// Configuration of the `Http` transport.
+///
+/// ```ignore
+/// let http = Http::builder("https://lotus.example.com/rpc/v0")
+///     .bearer_auth(token)
+///     .timeout(Duration::from_secs(10))
+///     .method_timeout("ChainReadObj", Duration::from_secs(300))
+///     .method_timeout("ClientImport", Duration::from_secs(3600))
+///     .root_certificate_pem(&std::fs::read("ca.pem")?)
+///     .build()?;
+/// ```
+pub struct HttpBuilder {
+    url: String,
+    bearer_auth: Option<String>,
+    connect_timeout: Duration,
+    timeout: Duration,
+    method_timeouts: HashMap<String, Duration>,
+    headers: Vec<(String, String)>,
+    user_agent: Option<String>,
+    proxy: Option<String>,
+    root_certificates: Vec<Vec<u8>>,
+    identity: Option<(Vec<u8>, String)>,
+    #[cfg(feature = "gzip")]
+    gzip: bool,
+    pool_max_idle_per_host: Option<usize>,
+    // `Some(None)` keeps the idle connections forever.
+    pool_idle_timeout: Option<Option<Duration>>,
+}
+
+impl HttpBuilder {
+    /// Builder with a 10s connect timeout and a 30s request timeout.
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.into(),
+            bearer_auth: None,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            timeout: DEFAULT_TIMEOUT,
+            method_timeouts: HashMap::new(),
+            headers: vec![],
+            user_agent: None,
+            proxy: None,
+            root_certificates: vec![],
+            identity: None,
+            #[cfg(feature = "gzip")]
+            gzip: false,
+            pool_max_idle_per_host: None,
+            pool_idle_timeout: None,
+        }
+    }
+
+    /// Send `token` as bearer auth with every request.
+    pub fn bearer_auth<T: Into<String>>(mut self, token: T) -> Self {
+        self.bearer_auth = Some(token.into());
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Timeout of a whole call, from connecting to reading the response.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Timeout of the calls of `method`, the `Filecoin.` namespace may be omitted.
+    pub fn method_timeout(mut self, method: &str, timeout: Duration) -> Self {
+        let method = if method.contains('.') {
+            method.to_string()
+        } else {
+            format!("Filecoin.{}", method)
+        };
+        self.method_timeouts.insert(method, timeout);
+        self
+    }
+
+    /// Header sent with every request.
+    pub fn header<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn user_agent<T: Into<String>>(mut self, user_agent: T) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Proxy all requests, e.g. `http://127.0.0.1:8080` or `socks5://127.0.0.1:1080`.
+    pub fn proxy<T: Into<String>>(mut self, url: T) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    /// Trust the PEM encoded CA certificate, in addition to the system ones.
+    pub fn root_certificate_pem(mut self, pem: &[u8]) -> Self {
+        self.root_certificates.push(pem.to_vec());
+        self
+    }
+
+    /// Authenticate with the DER encoded PKCS #12 archive of a client certificate and its key.
+    pub fn identity_pkcs12(mut self, der: &[u8], password: &str) -> Self {
+        self.identity = Some((der.to_vec(), password.into()));
+        self
+    }
+
+    /// Accept gzip compressed responses.
+    #[cfg(feature = "gzip")]
+    pub fn gzip(mut self, enable: bool) -> Self {
+        self.gzip = enable;
+        self
+    }
+
+    /// Maximum of idle connections kept per host.
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// How long idle connections are kept, `None` to keep them forever.
+    pub fn pool_idle_timeout<T: Into<Option<Duration>>>(mut self, timeout: T) -> Self {
+        self.pool_idle_timeout = Some(timeout.into());
+        self
+    }
+
+    pub fn build(self) -> Result<Http> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| Error::Config(format!("Invalid header name {}: {}", name, e)))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| Error::Config(format!("Invalid value of header {}: {}", name, e)))?;
+            headers.append(name, value);
+        }
+
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.timeout)
+            .default_headers(headers);
+        if let Some(user_agent) = self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        if let Some(proxy) = self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(&proxy)?);
+        }
+        for pem in &self.root_certificates {
+            builder = builder.add_root_certificate(reqwest::Certificate::from_pem(pem)?);
+        }
+        if let Some((der, password)) = &self.identity {
+            builder = builder.identity(reqwest::Identity::from_pkcs12_der(der, password)?);
+        }
+        #[cfg(feature = "gzip")]
+        {
+            builder = builder.gzip(self.gzip);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+
+        Ok(Http {
+            id: Default::default(),
+            url: self.url,
+            bearer_auth: self.bearer_auth,
+            client: builder.build()?,
+            method_timeouts: Arc::new(self.method_timeouts),
+            timeout: None,
+        })
     }
 }
 
@@ -61,15 +305,52 @@ impl Transport for Http {
 mod tests {
     use super::*;
     use jsonrpc_core::Value;
+    use serde_json::json;
     use tokio::runtime::Runtime;
+    use crate::testing::MockServer;
 
     #[test]
     fn basic_test() {
         let mut rt = Runtime::new().unwrap();
-        let http = Http::new("http://47.52.21.141:1234/rpc/v0");
+        let server = rt.enter(MockServer::start);
+        server.fixture("Version", json!({"Version": "0.4.0+git.7c9ac2ad", "APIVersion": 1792, "BlockDelay": 25}));
+        let http = Http::new(&server.url());
         // Filecoin.Version need read permission
         let version: Value = rt.block_on(http.send("Filecoin.Version", Params::Array(vec![]))).unwrap();
 
         println!("Version: {:?}", version);
+        assert_eq!(version["BlockDelay"], 25);
+    }
+
+    #[test]
+    fn builder() {
+        let http = Http::builder("http://127.0.0.1:1234/rpc/v0")
+            .header("X-Lotus-Client", "lotus-api")
+            .user_agent("lotus-api")
+            .method_timeout("ChainReadObj", Duration::from_secs(300))
+            .method_timeout("Filecoin.ClientImport", Duration::from_secs(3600))
+            .pool_idle_timeout(None)
+            .build()
+            .unwrap();
+        let (_, head) = http.prepare("Filecoin.ChainHead", Params::Array(vec![]));
+        let (_, read) = http.prepare("Filecoin.ChainReadObj", Params::Array(vec![]));
+        let (_, import) = http.prepare("Filecoin.ClientImport", Params::Array(vec![]));
+        assert_eq!(http.request_timeout(&Request::Single(head.clone())), None);
+        assert_eq!(http.request_timeout(&Request::Single(read.clone())), Some(Duration::from_secs(300)));
+        assert_eq!(
+            http.request_timeout(&Request::Batch(vec![head.clone(), import, read])),
+            Some(Duration::from_secs(3600))
+        );
+        let http = http.with_timeout(Duration::from_secs(1));
+        assert_eq!(http.request_timeout(&Request::Single(head)), Some(Duration::from_secs(1)));
+
+        let invalid_header = Http::builder("http://127.0.0.1:1234/rpc/v0")
+            .header("X-Invalid", "line\nbreak")
+            .build();
+        assert!(matches!(invalid_header, Err(Error::Config(_))));
+        let invalid_certificate = Http::builder("http://127.0.0.1:1234/rpc/v0")
+            .root_certificate_pem(b"not a certificate")
+            .build();
+        assert!(invalid_certificate.is_err());
     }
 }
\ No newline at end of file
@@ -1,15 +1,34 @@
+use std::collections::HashMap;
 use log::{debug};
-use jsonrpc_core::{Response, Output, Request, Params, Call};
+use jsonrpc_core::{Response, Output, Request, Params, Call, Id};
 use serde::de::DeserializeOwned;
-use crate::error::Result;
+use serde_json::Value;
+use futures::channel::mpsc;
+use crate::error::{Error, Result};
 
 mod http;
+mod ws;
+mod cassette;
+mod api_info;
+mod retry;
+mod balanced;
 
 pub use self::http::*;
+pub use self::ws::*;
+pub use self::cassette::{Record, Replay};
+pub use self::api_info::{ApiInfo, NodeType};
+pub use self::retry::{Retry, RetryConfig, IDEMPOTENT_METHODS};
+pub use self::balanced::{Balanced, Policy, EndpointHealth};
 
 /// Assigned RequestId
 pub type RequestId = usize;
 
+/// Channel id assigned by the node to a subscription.
+pub type SubscriptionId = u64;
+
+/// Raw notification values pushed by the node for one subscription.
+pub type NotificationReceiver = mpsc::UnboundedReceiver<Value>;
+
 #[async_trait::async_trait]
 pub trait Transport {
     /// Prepare serializable RPC call for given method with parameters.
@@ -25,23 +44,157 @@ pub trait Transport {
             T: DeserializeOwned,
     {
         let (id, call) = self.prepare(method, params);
-        let request = Request::Single(call);
-        debug!(
-            "Request: {}",
-            serde_json::to_string(&request).expect("Serialize `Request` never fails")
-        );
-
-        let response = self.execute(id, &request).await?;
-        debug!(
-            "Response: {}",
-            serde_json::to_string(&response).expect("Serialize `Response` never fails")
-        );
-        match response {
-            Response::Single(Output::Success(success)) => {
-                Ok(serde_json::from_value(success.result)?)
-            }
-            Response::Single(Output::Failure(failure)) => Err(failure.error.into()),
-            Response::Batch(_) => panic!("Expected single, got batch"),
+        execute_single(self, id, &Request::Single(call)).await
+    }
+
+    /// Send remote methods in a single batch request.
+    ///
+    /// The results are returned in the order of `requests`, each with its own error.
+    async fn send_batch<M>(&self, requests: Vec<(M, Params)>) -> Result<Vec<Result<Value>>>
+        where
+            M: Into<String> + Send,
+    {
+        let (ids, calls): (Vec<RequestId>, Vec<Call>) = requests
+            .into_iter()
+            .map(|(method, params)| self.prepare(method, params))
+            .unzip();
+        execute_batch(self, &ids, &Request::Batch(calls)).await
+    }
+}
+
+/// Execute a single call prepared by `transport`, and parse its result.
+pub(crate) async fn execute_single<R, T>(transport: &R, id: RequestId, request: &Request) -> Result<T>
+    where
+        R: Transport + Sync + ?Sized,
+        T: DeserializeOwned,
+{
+    debug!(
+        "Request: {}",
+        serde_json::to_string(request).expect("Serialize `Request` never fails")
+    );
+
+    let response = transport.execute(id, request).await?;
+    debug!(
+        "Response: {}",
+        serde_json::to_string(&response).expect("Serialize `Response` never fails")
+    );
+    match response {
+        Response::Single(Output::Success(success)) => {
+            Ok(serde_json::from_value(success.result)?)
+        }
+        Response::Single(Output::Failure(failure)) => Err(failure.error.into()),
+        Response::Batch(_) => Err(Error::Protocol("Expected single, got batch".into())),
+    }
+}
+
+/// Execute a batch of calls prepared by `transport` with `ids`, and return their results in order.
+pub(crate) async fn execute_batch<R>(transport: &R, ids: &[RequestId], request: &Request) -> Result<Vec<Result<Value>>>
+    where
+        R: Transport + Sync + ?Sized,
+{
+    let id = match ids.first() {
+        Some(id) => *id,
+        None => return Ok(vec![]),
+    };
+    debug!(
+        "Request: {}",
+        serde_json::to_string(request).expect("Serialize `Request` never fails")
+    );
+
+    let response = transport.execute(id, request).await?;
+    debug!(
+        "Response: {}",
+        serde_json::to_string(&response).expect("Serialize `Response` never fails")
+    );
+    let outputs = match response {
+        Response::Batch(outputs) => outputs,
+        // A failure for the batch as a whole, e.g. the server doesn't support batches.
+        Response::Single(Output::Failure(failure)) if ids.len() > 1 => return Err(failure.error.into()),
+        Response::Single(output) => vec![output],
+    };
+
+    // The server may answer the calls of a batch in any order.
+    let mut outputs = outputs
+        .into_iter()
+        .filter_map(|output| match output.id() {
+            Id::Num(id) => Some((*id as RequestId, output)),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    Ok(ids
+        .iter()
+        .map(|id| match outputs.remove(id) {
+            Some(Output::Success(success)) => Ok(success.result),
+            Some(Output::Failure(failure)) => Err(failure.error.into()),
+            None => Err(Error::Protocol(format!("Missing response for request {}", id))),
+        })
+        .collect())
+}
+
+/// A transport that can also receive values pushed by the node, such as the
+/// channels returned by `ChainNotify`, `MpoolSub` and `SyncIncomingBlocks`.
+#[async_trait::async_trait]
+pub trait DuplexTransport: Transport {
+    /// Execute prepared RPC call whose result is a notification channel.
+    async fn subscribe(&self, id: RequestId, request: &Request) -> Result<(SubscriptionId, NotificationReceiver)>;
+
+    /// Close the notification channel.
+    async fn unsubscribe(&self, id: SubscriptionId) -> Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpc_core::{Failure, Success, Version, MethodCall, ErrorCode};
+    use tokio::runtime::Runtime;
+
+    // Answers the calls of a batch in reverse order, failing the odd ids.
+    struct Reversed;
+
+    #[async_trait::async_trait]
+    impl Transport for Reversed {
+        fn prepare<M: Into<String>>(&self, method: M, params: Params) -> (RequestId, Call) {
+            let id = params.clone().parse::<Vec<usize>>().unwrap()[0];
+            let call = Call::MethodCall(MethodCall {
+                jsonrpc: Some(Version::V2),
+                id: Id::Num(id as u64),
+                method: method.into(),
+                params,
+            });
+            (id, call)
+        }
+
+        async fn execute(&self, _id: RequestId, request: &Request) -> Result<Response> {
+            let calls = match request {
+                Request::Batch(calls) => calls,
+                Request::Single(_) => unreachable!(),
+            };
+            Ok(Response::Batch(calls.iter().rev().map(|call| match call {
+                Call::MethodCall(MethodCall { id: Id::Num(n), .. }) if n % 2 == 1 => Output::Failure(Failure {
+                    jsonrpc: Some(Version::V2),
+                    error: jsonrpc_core::Error::new(ErrorCode::InternalError),
+                    id: Id::Num(*n),
+                }),
+                Call::MethodCall(MethodCall { id, .. }) => Output::Success(Success {
+                    jsonrpc: Some(Version::V2),
+                    result: serde_json::to_value(id).unwrap(),
+                    id: id.clone(),
+                }),
+                _ => unreachable!(),
+            }).collect()))
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn batch_results_in_order() {
+        let mut rt = Runtime::new().unwrap();
+        let requests = (0..4).map(|i| ("Echo", Params::Array(vec![Value::from(i)]))).collect();
+        let results = rt.block_on(Reversed.send_batch(requests)).unwrap();
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap(), &Value::from(0));
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().unwrap(), &Value::from(2));
+        assert!(results[3].is_err());
+    }
+}
@@ -0,0 +1,339 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use jsonrpc_core::{Request, Response, Params, Call, MethodCall};
+use log::{debug, warn};
+use rand::Rng;
+use crate::error::{Error, Result};
+use crate::transports::{Transport, RequestId};
+
+/// Read-only methods, which are safe to send again after a failure.
+pub const IDEMPOTENT_METHODS: &[&str] = &[
+    "Filecoin.AuthVerify",
+    "Filecoin.ID",
+    "Filecoin.Version",
+    "Filecoin.LogList",
+    "Filecoin.NetConnectedness",
+    "Filecoin.NetPeers",
+    "Filecoin.NetAddrsListen",
+    "Filecoin.NetFindPeer",
+    "Filecoin.ChainHead",
+    "Filecoin.ChainGetRandomness",
+    "Filecoin.ChainGetBlock",
+    "Filecoin.ChainGetTipSet",
+    "Filecoin.ChainGetBlockMessages",
+    "Filecoin.ChainGetParentReceipts",
+    "Filecoin.ChainGetParentMessages",
+    "Filecoin.ChainGetTipSetByHeight",
+    "Filecoin.ChainReadObj",
+    "Filecoin.ChainHasObj",
+    "Filecoin.ChainStatObj",
+    "Filecoin.ChainGetGenesis",
+    "Filecoin.ChainTipSetWeight",
+    "Filecoin.ChainGetMessage",
+    "Filecoin.ChainGetPath",
+    "Filecoin.WalletHas",
+    "Filecoin.WalletList",
+    "Filecoin.WalletBalance",
+    "Filecoin.WalletVerify",
+    "Filecoin.WalletDefaultAddress",
+    "Filecoin.SyncState",
+    "Filecoin.SyncCheckBad",
+    "Filecoin.MpoolPending",
+    "Filecoin.MpoolGetNonce",
+    "Filecoin.MpoolEstimateGasPrice",
+    "Filecoin.GasEstimateFeeCap",
+    "Filecoin.GasEstimateGasPremium",
+    "Filecoin.GasEstimateGasLimit",
+    "Filecoin.GasEstimateMessageGas",
+    "Filecoin.MinerGetBaseInfo",
+    "Filecoin.ClientGetDealInfo",
+    "Filecoin.ClientListDeals",
+    "Filecoin.ClientHasLocal",
+    "Filecoin.ClientFindData",
+    "Filecoin.ClientListImports",
+    "Filecoin.StateGetActor",
+    "Filecoin.StateLookupID",
+    "Filecoin.StateAccountKey",
+    "Filecoin.StateMinerPower",
+    "Filecoin.StateMinerInfo",
+    "Filecoin.StateMinerSectors",
+    "Filecoin.StateListMiners",
+    "Filecoin.StateListActors",
+    "Filecoin.StateMarketBalance",
+    "Filecoin.StateMarketDeals",
+    "Filecoin.StateNetworkName",
+    "Filecoin.StateCall",
+    "Filecoin.StateReplay",
+    "Filecoin.StateWaitMsg",
+    "Filecoin.StateSearchMsg",
+];
+
+/// Configuration of the `Retry` transport.
+#[derive(Clone, Debug)]
+pub struct RetryConfig {
+    /// Retries of a failed idempotent call, after the first attempt.
+    pub max_retries: usize,
+    /// Delay before the first retry, doubled by `multiplier` at each retry.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+    /// Consecutive failures which open the circuit breaker.
+    pub failure_threshold: usize,
+    /// How long the breaker stays open before letting a trial call through.
+    pub reset_timeout: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            failure_threshold: 5,
+            reset_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Breaker {
+    consecutive_failures: usize,
+    opened_at: Option<Instant>,
+    /// When the trial call of the half-open breaker was let through.
+    trial_at: Option<Instant>,
+}
+
+/// Transport retrying the idempotent calls of the inner transport which fail with a
+/// retryable error (see `Error::is_retryable`), with jittered exponential backoff.
+///
+/// After `failure_threshold` consecutive failures the circuit breaker opens, and every
+/// call fails with `Error::Unreachable` until `reset_timeout` has elapsed. A single trial
+/// call is then let through, which closes the breaker if it succeeds.
+pub struct Retry<T> {
+    inner: T,
+    config: RetryConfig,
+    idempotent: HashSet<String>,
+    breaker: Mutex<Breaker>,
+}
+
+impl<T: Transport> Retry<T> {
+    pub fn new(inner: T) -> Self {
+        Self::with_config(inner, RetryConfig::default())
+    }
+
+    pub fn with_config(inner: T, config: RetryConfig) -> Self {
+        Self {
+            inner,
+            config,
+            idempotent: IDEMPOTENT_METHODS.iter().map(|method| method.to_string()).collect(),
+            breaker: Default::default(),
+        }
+    }
+
+    /// Also retry `method`, e.g. `Filecoin.StateMinerDeadlines`.
+    pub fn idempotent<M: Into<String>>(mut self, method: M) -> Self {
+        self.idempotent.insert(method.into());
+        self
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn is_idempotent(&self, request: &Request) -> bool {
+        let idempotent = |call: &Call| match call {
+            Call::MethodCall(MethodCall { method, .. }) => self.idempotent.contains(method),
+            _ => false,
+        };
+        match request {
+            Request::Single(call) => idempotent(call),
+            Request::Batch(calls) => calls.iter().all(idempotent),
+        }
+    }
+
+    // Fails when the breaker is open, half-opening it once the reset timeout has elapsed.
+    fn check_breaker(&self) -> Result<()> {
+        let mut breaker = self.breaker.lock().expect("lock poisoned");
+        match (breaker.opened_at, breaker.trial_at) {
+            (Some(opened_at), _) if opened_at.elapsed() < self.config.reset_timeout => Err(Error::Unreachable),
+            // Let one trial call through, the others still fail until it completes. A trial
+            // call which never completes, e.g. dropped, is replaced after the reset timeout.
+            (Some(_), Some(trial_at)) if trial_at.elapsed() < self.config.reset_timeout => Err(Error::Unreachable),
+            (Some(_), _) => {
+                breaker.trial_at = Some(Instant::now());
+                Ok(())
+            }
+            (None, _) => Ok(()),
+        }
+    }
+
+    fn record(&self, success: bool) {
+        let mut breaker = self.breaker.lock().expect("lock poisoned");
+        if success {
+            *breaker = Breaker::default();
+            return;
+        }
+        breaker.consecutive_failures += 1;
+        breaker.trial_at = None;
+        if breaker.consecutive_failures >= self.config.failure_threshold {
+            if breaker.opened_at.is_none() {
+                warn!("Circuit breaker opened after {} failures", breaker.consecutive_failures);
+            }
+            breaker.opened_at = Some(Instant::now());
+        }
+    }
+
+    fn backoff(&self, retry: usize) -> Duration {
+        let backoff = self.config.initial_backoff.as_secs_f64() * self.config.multiplier.powi(retry as i32);
+        let backoff = backoff.min(self.config.max_backoff.as_secs_f64());
+        // Jitter, so that the clients failing together don't retry together.
+        Duration::from_secs_f64(rand::thread_rng().gen_range(backoff / 2.0, backoff.max(f64::EPSILON)))
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Transport + Send + Sync> Transport for Retry<T> {
+    fn prepare<M: Into<String>>(&self, method: M, params: Params) -> (RequestId, Call) {
+        self.inner.prepare(method, params)
+    }
+
+    async fn execute(&self, id: RequestId, request: &Request) -> Result<Response> {
+        let max_retries = if self.is_idempotent(request) {
+            self.config.max_retries
+        } else {
+            0
+        };
+
+        let mut retry = 0;
+        loop {
+            self.check_breaker()?;
+            match self.inner.execute(id, request).await {
+                Err(e) if e.is_retryable() => {
+                    self.record(false);
+                    if retry >= max_retries {
+                        return Err(e);
+                    }
+                    let backoff = self.backoff(retry);
+                    debug!("Request {} failed: {}, retrying in {:?}", id, e, backoff);
+                    tokio::time::delay_for(backoff).await;
+                    retry += 1;
+                }
+                result => {
+                    self.record(true);
+                    return result;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use jsonrpc_core::{Id, Output, Success, Version};
+    use serde_json::Value;
+    use tokio::runtime::Runtime;
+
+    // Fails the first `failures` calls with a transport error.
+    struct Flaky {
+        failures: usize,
+        calls: AtomicUsize,
+    }
+
+    impl Flaky {
+        fn new(failures: usize) -> Self {
+            Self { failures, calls: AtomicUsize::new(0) }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Transport for Flaky {
+        fn prepare<M: Into<String>>(&self, method: M, params: Params) -> (RequestId, Call) {
+            let call = Call::MethodCall(MethodCall {
+                jsonrpc: Some(Version::V2),
+                id: Id::Num(1),
+                method: method.into(),
+                params,
+            });
+            (1, call)
+        }
+
+        async fn execute(&self, _id: RequestId, _request: &Request) -> Result<Response> {
+            if self.calls.fetch_add(1, Ordering::AcqRel) < self.failures {
+                return Err(Error::Transport("connection refused".into()));
+            }
+            Ok(Response::Single(Output::Success(Success {
+                jsonrpc: Some(Version::V2),
+                result: Value::Bool(true),
+                id: Id::Num(1),
+            })))
+        }
+    }
+
+    fn config() -> RetryConfig {
+        RetryConfig {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+            multiplier: 2.0,
+            failure_threshold: 3,
+            reset_timeout: Duration::from_secs(3600),
+        }
+    }
+
+    #[test]
+    fn retry_idempotent_methods() {
+        let mut rt = Runtime::new().unwrap();
+        let retry = Retry::with_config(Flaky::new(2), config());
+        let head: bool = rt.block_on(retry.send("Filecoin.ChainHead", Params::Array(vec![]))).unwrap();
+        assert!(head);
+        assert_eq!(retry.inner.calls.load(Ordering::Acquire), 3);
+
+        let retry = Retry::with_config(Flaky::new(1), config());
+        let push = rt.block_on(retry.send::<_, bool>("Filecoin.MpoolPush", Params::Array(vec![])));
+        assert_eq!(push, Err(Error::Transport("connection refused".into())));
+        assert_eq!(retry.inner.calls.load(Ordering::Acquire), 1);
+
+        let retry = Retry::with_config(Flaky::new(1), config()).idempotent("Filecoin.MpoolPush");
+        assert!(rt.block_on(retry.send::<_, bool>("Filecoin.MpoolPush", Params::Array(vec![]))).is_ok());
+    }
+
+    // Let the reset timeout of the open breaker elapse, without waiting for it.
+    fn elapse_reset_timeout<T>(retry: &Retry<T>) {
+        let mut breaker = retry.breaker.lock().unwrap();
+        breaker.opened_at = breaker.opened_at.map(|opened_at| opened_at - retry.config.reset_timeout);
+    }
+
+    #[test]
+    fn circuit_breaker() {
+        let mut rt = Runtime::new().unwrap();
+        let retry = Retry::with_config(Flaky::new(4), config());
+
+        // 3 attempts, which open the breaker.
+        let head = rt.block_on(retry.send::<_, bool>("Filecoin.ChainHead", Params::Array(vec![])));
+        assert_eq!(head, Err(Error::Transport("connection refused".into())));
+        let head = rt.block_on(retry.send::<_, bool>("Filecoin.ChainHead", Params::Array(vec![])));
+        assert_eq!(head, Err(Error::Unreachable));
+        assert_eq!(retry.inner.calls.load(Ordering::Acquire), 3);
+
+        // The trial call fails, which opens the breaker again.
+        elapse_reset_timeout(&retry);
+        let push = rt.block_on(retry.send::<_, bool>("Filecoin.MpoolPush", Params::Array(vec![])));
+        assert_eq!(push, Err(Error::Transport("connection refused".into())));
+        let head = rt.block_on(retry.send::<_, bool>("Filecoin.ChainHead", Params::Array(vec![])));
+        assert_eq!(head, Err(Error::Unreachable));
+
+        // A single trial call at a time.
+        elapse_reset_timeout(&retry);
+        assert_eq!(retry.check_breaker(), Ok(()));
+        assert_eq!(retry.check_breaker(), Err(Error::Unreachable));
+        retry.breaker.lock().unwrap().trial_at = None;
+
+        // The trial call succeeds, which closes the breaker.
+        assert_eq!(rt.block_on(retry.send::<_, bool>("Filecoin.ChainHead", Params::Array(vec![]))), Ok(true));
+        assert_eq!(rt.block_on(retry.send::<_, bool>("Filecoin.ChainHead", Params::Array(vec![]))), Ok(true));
+    }
+}
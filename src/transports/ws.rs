@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+use log::{debug, error, warn};
+use futures::{select, Sink, SinkExt, Stream, StreamExt};
+use futures::channel::{mpsc, oneshot};
+use jsonrpc_core::{Request, Response, Output, Params, Call, Version, MethodCall, Notification, Id};
+use serde_json::Value;
+use tokio_tungstenite::tungstenite::{Message, Error as WsError};
+use crate::error::{Error, Result};
+use crate::transports::{Transport, DuplexTransport, RequestId, SubscriptionId, NotificationReceiver};
+
+// Methods used by the channel protocol of Lotus (go-jsonrpc).
+const CHANNEL_VALUE: &str = "xrpc.ch.val";
+const CHANNEL_CLOSE: &str = "xrpc.ch.close";
+const CANCEL: &str = "xrpc.cancel";
+
+type Pending = oneshot::Sender<Result<Response>>;
+type PendingSubscription = oneshot::Sender<Result<(SubscriptionId, NotificationReceiver)>>;
+
+enum TransportMessage {
+    Request { id: RequestId, request: String, sender: Pending },
+    Subscribe { id: RequestId, request: String, sender: PendingSubscription },
+    Unsubscribe { id: SubscriptionId },
+}
+
+/// WebSocket transport, required for the subscription methods.
+///
+/// The connection is driven by a background task, which is stopped and all
+/// notification streams are closed once the last `Ws` handle is dropped.
+#[derive(Clone)]
+pub struct Ws {
+    id: Arc<AtomicUsize>,
+    sender: mpsc::UnboundedSender<TransportMessage>,
+}
+
+impl Ws {
+    /// Connect to the given endpoint, e.g. `ws://127.0.0.1:1234/rpc/v0`.
+    pub async fn new(url: &str) -> Result<Self> {
+        let (socket, _) = tokio_tungstenite::connect_async(url).await?;
+        let (sender, receiver) = mpsc::unbounded();
+        tokio::spawn(run(socket, receiver));
+
+        Ok(Self {
+            id: Default::default(),
+            sender,
+        })
+    }
+
+    fn send_message(&self, message: TransportMessage) -> Result<()> {
+        self.sender
+            .unbounded_send(message)
+            .map_err(|_| Error::Unreachable)
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for Ws {
+    fn prepare<M: Into<String>>(&self, method: M, params: Params) -> (RequestId, Call) {
+        let id = self.id.fetch_add(1, Ordering::AcqRel);
+        let call = Call::MethodCall(MethodCall {
+            jsonrpc: Some(Version::V2),
+            id: Id::Num(id as u64),
+            method: method.into(),
+            params,
+        });
+        (id, call)
+    }
+
+    async fn execute(&self, id: RequestId, request: &Request) -> Result<Response> {
+        let request = serde_json::to_string(request)?;
+        let (sender, receiver) = oneshot::channel();
+        self.send_message(TransportMessage::Request { id, request, sender })?;
+        receiver.await.map_err(|_| Error::Unreachable)?
+    }
+}
+
+#[async_trait::async_trait]
+impl DuplexTransport for Ws {
+    async fn subscribe(&self, id: RequestId, request: &Request) -> Result<(SubscriptionId, NotificationReceiver)> {
+        let request = serde_json::to_string(request)?;
+        let (sender, receiver) = oneshot::channel();
+        self.send_message(TransportMessage::Subscribe { id, request, sender })?;
+        receiver.await.map_err(|_| Error::Unreachable)?
+    }
+
+    async fn unsubscribe(&self, id: SubscriptionId) -> Result<()> {
+        self.send_message(TransportMessage::Unsubscribe { id })
+    }
+}
+
+async fn run<S>(socket: S, mut messages: mpsc::UnboundedReceiver<TransportMessage>)
+    where
+        S: Stream<Item = std::result::Result<Message, WsError>> + Sink<Message, Error = WsError>,
+{
+    let (mut sink, stream) = socket.split();
+    let mut stream = stream.fuse();
+    let mut task = WsTask::default();
+
+    loop {
+        let outgoing = select! {
+            message = messages.next() => match message {
+                Some(message) => task.outgoing(message),
+                None => break,
+            },
+            message = stream.next() => match message {
+                Some(Ok(Message::Text(text))) => task.incoming(&text),
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => None,
+                Some(Err(e)) => {
+                    error!("WebSocket read error: {:?}", e);
+                    break;
+                }
+            },
+        };
+
+        if let Some(text) = outgoing {
+            if let Err(e) = sink.send(Message::Text(text)).await {
+                error!("WebSocket write error: {:?}", e);
+                break;
+            }
+        }
+    }
+    // Dropping the task fails the pending calls and closes the subscriptions.
+    debug!("WebSocket connection closed");
+}
+
+#[derive(Default)]
+struct WsTask {
+    pending: HashMap<RequestId, Pending>,
+    pending_subscriptions: HashMap<RequestId, PendingSubscription>,
+    // channel id -> (id of the request which opened the channel, notification sender)
+    subscriptions: HashMap<SubscriptionId, (RequestId, mpsc::UnboundedSender<Value>)>,
+}
+
+impl WsTask {
+    /// Register an outgoing message, returning the text to write to the socket.
+    fn outgoing(&mut self, message: TransportMessage) -> Option<String> {
+        match message {
+            TransportMessage::Request { id, request, sender } => {
+                self.pending.insert(id, sender);
+                Some(request)
+            }
+            TransportMessage::Subscribe { id, request, sender } => {
+                self.pending_subscriptions.insert(id, sender);
+                Some(request)
+            }
+            TransportMessage::Unsubscribe { id } => {
+                let (request_id, _) = self.subscriptions.remove(&id)?;
+                Some(cancel_request(request_id))
+            }
+        }
+    }
+
+    /// Route an incoming text message, returning the text to write back to the socket if any.
+    fn incoming(&mut self, text: &str) -> Option<String> {
+        let value: Value = match serde_json::from_str(text) {
+            Ok(value) => value,
+            Err(e) => {
+                warn!("Invalid WebSocket message: {:?}", e);
+                return None;
+            }
+        };
+
+        if value.get("method").is_some() {
+            self.notification(value)
+        } else {
+            match serde_json::from_value::<Response>(value) {
+                Ok(response) => self.response(response),
+                Err(e) => warn!("Invalid JSON-RPC response: {:?}", e),
+            }
+            None
+        }
+    }
+
+    fn response(&mut self, response: Response) {
+        let id = match &response {
+            Response::Single(output) => match output.id() {
+                Id::Num(id) => *id as RequestId,
+                id => {
+                    warn!("Unexpected response id: {:?}", id);
+                    return;
+                }
+            },
+            // A batch is registered with the id of its first call, but may be answered in any order.
+            Response::Batch(outputs) => {
+                let id = outputs.iter().find_map(|output| match output.id() {
+                    Id::Num(id) if self.pending.contains_key(&(*id as RequestId)) => Some(*id as RequestId),
+                    _ => None,
+                });
+                match id {
+                    Some(id) => id,
+                    None => {
+                        warn!("Unexpected batch response");
+                        return;
+                    }
+                }
+            }
+        };
+
+        if let Some(sender) = self.pending.remove(&id) {
+            let _ = sender.send(Ok(response));
+        } else if let Some(sender) = self.pending_subscriptions.remove(&id) {
+            let result = match response {
+                Response::Single(Output::Success(success)) => match success.result.as_u64() {
+                    Some(channel) => {
+                        let (notifications, receiver) = mpsc::unbounded();
+                        self.subscriptions.insert(channel, (id, notifications));
+                        Ok((channel, receiver))
+                    }
+                    None => Err(Error::Json(format!("Expected channel id, got {}", success.result))),
+                },
+                Response::Single(Output::Failure(failure)) => Err(failure.error.into()),
+                Response::Batch(_) => Err(Error::Protocol("Expected single, got batch".into())),
+            };
+            let _ = sender.send(result);
+        } else {
+            warn!("Response for unknown request: {}", id);
+        }
+    }
+
+    fn notification(&mut self, value: Value) -> Option<String> {
+        let notification: Notification = match serde_json::from_value(value) {
+            Ok(notification) => notification,
+            Err(e) => {
+                warn!("Invalid JSON-RPC notification: {:?}", e);
+                return None;
+            }
+        };
+        let mut params = match notification.params {
+            Params::Array(params) => params.into_iter(),
+            _ => return None,
+        };
+        let channel = params.next().as_ref().and_then(Value::as_u64)?;
+
+        match notification.method.as_str() {
+            CHANNEL_VALUE => {
+                let (request_id, sender) = match self.subscriptions.get(&channel) {
+                    Some(subscription) => subscription,
+                    None => {
+                        debug!("Notification for unknown channel: {}", channel);
+                        return None;
+                    }
+                };
+                if sender.unbounded_send(params.next().unwrap_or(Value::Null)).is_err() {
+                    // The notification stream has been dropped.
+                    let request_id = *request_id;
+                    self.subscriptions.remove(&channel);
+                    return Some(cancel_request(request_id));
+                }
+            }
+            CHANNEL_CLOSE => {
+                self.subscriptions.remove(&channel);
+            }
+            method => warn!("Unexpected notification: {}", method),
+        }
+        None
+    }
+}
+
+fn cancel_request(id: RequestId) -> String {
+    let request = Request::Single(Call::Notification(Notification {
+        jsonrpc: Some(Version::V2),
+        method: CANCEL.into(),
+        params: Params::Array(vec![Value::from(id as u64)]),
+    }));
+    serde_json::to_string(&request).expect("Serialize `Request` never fails")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::net::TcpListener;
+    use tokio::runtime::Runtime;
+    use tokio_tungstenite::WebSocketStream;
+    use crate::api::ChainApi;
+    use crate::testing::MockChain;
+
+    // The next text message of the client, e.g. a call.
+    async fn receive<S>(socket: &mut WebSocketStream<S>) -> Value
+        where
+            S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        match socket.next().await {
+            Some(Ok(Message::Text(text))) => serde_json::from_str(&text).unwrap(),
+            message => panic!("unexpected message {:?}", message),
+        }
+    }
+
+    async fn send<S>(socket: &mut WebSocketStream<S>, value: Value)
+        where
+            S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        socket.send(Message::Text(value.to_string())).await.unwrap();
+    }
+
+    #[test]
+    fn chain_notify_over_the_wire() {
+        let mut rt = Runtime::new().unwrap();
+        let mut listener = rt.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let url = format!("ws://{}/rpc/v0", listener.local_addr().unwrap());
+        let head = MockChain::new().add("1", 1, None);
+        let changes = json!([{"Type": "current", "Val": head}]);
+
+        // A node answering the calls of the client, in order.
+        let node = {
+            let (head, changes) = (head.clone(), changes.clone());
+            rt.spawn(async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+
+                // A subscription, closed by the node after one value.
+                let call = receive(&mut socket).await;
+                assert_eq!(call["method"], "Filecoin.ChainNotify");
+                send(&mut socket, json!({"jsonrpc": "2.0", "id": call["id"], "result": 1})).await;
+                send(&mut socket, json!({"jsonrpc": "2.0", "method": CHANNEL_VALUE, "params": [1, changes]})).await;
+                send(&mut socket, json!({"jsonrpc": "2.0", "method": CHANNEL_CLOSE, "params": [1]})).await;
+
+                // A subscription, dropped by the client after one value.
+                let subscription = receive(&mut socket).await;
+                send(&mut socket, json!({"jsonrpc": "2.0", "id": subscription["id"], "result": 2})).await;
+                send(&mut socket, json!({"jsonrpc": "2.0", "method": CHANNEL_VALUE, "params": [2, changes]})).await;
+
+                let call = receive(&mut socket).await;
+                assert_eq!(call["method"], "Filecoin.ChainHead");
+                send(&mut socket, json!({"jsonrpc": "2.0", "method": CHANNEL_VALUE, "params": [2, changes]})).await;
+                send(&mut socket, json!({"jsonrpc": "2.0", "id": call["id"], "result": head})).await;
+                let cancel = receive(&mut socket).await;
+                assert_eq!(cancel["method"], CANCEL);
+                assert_eq!(cancel["params"], json!([subscription["id"]]));
+
+                socket.close(None).await.unwrap();
+            })
+        };
+
+        rt.block_on(async {
+            let ws = Ws::new(&url).await.unwrap();
+
+            let (channel, notifications) = ws.chain_notify().await.unwrap();
+            assert_eq!(channel, 1);
+            let received = notifications.collect::<Vec<_>>().await;
+            assert_eq!(received.len(), 1);
+            assert_eq!(received[0].as_ref().unwrap()[0].val.key(), head.key());
+
+            let (channel, mut notifications) = ws.chain_notify().await.unwrap();
+            assert_eq!(channel, 2);
+            assert!(notifications.next().await.unwrap().is_ok());
+            drop(notifications);
+
+            // The next value of the dropped stream cancels the subscription.
+            assert_eq!(ws.chain_head().await.unwrap().key(), head.key());
+
+            node.await.unwrap();
+            let e = ws.chain_head().await.unwrap_err();
+            assert_eq!(e.kind(), &Error::Unreachable);
+        });
+    }
+
+    #[test]
+    fn subscription_routing() {
+        let mut task = WsTask::default();
+        let (sender, mut receiver) = oneshot::channel();
+        task.outgoing(TransportMessage::Subscribe { id: 7, request: String::new(), sender });
+
+        task.incoming(r#"{"jsonrpc":"2.0","id":7,"result":3}"#);
+        let (channel, mut notifications) = receiver.try_recv().unwrap().unwrap().unwrap();
+        assert_eq!(channel, 3);
+
+        task.incoming(r#"{"jsonrpc":"2.0","method":"xrpc.ch.val","params":[3,{"Height":1}]}"#);
+        assert_eq!(notifications.try_next().unwrap(), Some(serde_json::json!({"Height": 1})));
+
+        task.incoming(r#"{"jsonrpc":"2.0","method":"xrpc.ch.close","params":[3]}"#);
+        assert_eq!(notifications.try_next().unwrap(), None);
+
+        assert_eq!(
+            cancel_request(7),
+            r#"{"jsonrpc":"2.0","method":"xrpc.cancel","params":[7]}"#
+        );
+    }
+}
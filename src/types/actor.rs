@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use cid::Cid;
+use super::utils::cid_json;
+use super::address::Address;
+use super::cbor::{CborDecode, CborError, Decoder};
+use super::token::TokenAmount;
+
+/// The state of an actor.
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Actor {
+    /// The CID of the actor code.
+    #[serde(with = "cid_json")]
+    pub code: Cid,
+    /// The CID of the root of the actor state.
+    #[serde(with = "cid_json")]
+    pub head: Cid,
+    /// The nonce of the next message sent by the actor.
+    pub nonce: u64,
+    /// The balance in attoFIL.
+    pub balance: TokenAmount,
+}
+
+/// The return of `InitActor.Exec` (method 2), the addresses of the created actor.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct ExecReturn {
+    /// The ID address, which may change on a reorg.
+    pub id_address: Address,
+    /// The robust address, which never changes.
+    pub robust_address: Address,
+}
+
+impl CborDecode for ExecReturn {
+    fn decode_cbor(decoder: &mut Decoder<'_>) -> Result<Self, CborError> {
+        decoder.read_tuple_header(2)?;
+        Ok(Self {
+            id_address: Address::decode_cbor(decoder)?,
+            robust_address: Address::decode_cbor(decoder)?,
+        })
+    }
+}
+
+/// The return of `PowerActor.CreateMiner` (method 2), the addresses of the created miner.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct CreateMinerReturn {
+    /// The ID address, which may change on a reorg.
+    pub id_address: Address,
+    /// The robust address, which never changes.
+    pub robust_address: Address,
+}
+
+impl CborDecode for CreateMinerReturn {
+    fn decode_cbor(decoder: &mut Decoder<'_>) -> Result<Self, CborError> {
+        let ExecReturn { id_address, robust_address } = ExecReturn::decode_cbor(decoder)?;
+        Ok(Self { id_address, robust_address })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn actor_json() {
+        let json = r#"{"Code":{"/":"bafkqadlgnfwc6mjpmfrwg33vnz2a"},"Head":{"/":"bafy2bzaceae7pqh2wupmp3fqnlbsxx2czjku5rbisl3qdtaa5mehs2hkjak3a"},"Nonce":3,"Balance":"1000000000000000000"}"#;
+        let actor = serde_json::from_str::<Actor>(json).unwrap();
+        assert_eq!(actor.nonce, 3);
+        assert_eq!(actor.balance, TokenAmount::from_fil(1));
+        assert_eq!(serde_json::to_string(&actor).unwrap(), json);
+    }
+
+    #[test]
+    fn exec_return_cbor() {
+        use crate::types::{ExitCode, MessageReceipt};
+
+        let json = r#"{"ExitCode":0,"Return":"gkMA6wdVAqqqqqqqqqqqqqqqqqqqqqqqqqqq","GasUsed":"1000"}"#;
+        let receipt = serde_json::from_str::<MessageReceipt>(json).unwrap();
+        assert!(receipt.is_success());
+        let exec = receipt.decode_return::<ExecReturn>().unwrap();
+        assert_eq!(exec.id_address, Address::new_id_addr(1003).unwrap());
+        assert_eq!(exec.robust_address.as_bytes(), [&[2u8][..], &[0xaa; 20]].concat());
+        assert_eq!(serde_json::to_string(&receipt).unwrap(), json);
+
+        let json = r#"{"ExitCode":16,"Return":"","GasUsed":"1000"}"#;
+        let receipt = serde_json::from_str::<MessageReceipt>(json).unwrap();
+        assert_eq!(receipt.exit_code, ExitCode::ErrIllegalArgument);
+        assert_eq!(receipt.decode_return::<ExecReturn>(), Err(CborError::UnexpectedEof));
+    }
+}
@@ -4,6 +4,7 @@ use std::str::FromStr;
 use std::fmt::{self, Display};
 use std::convert::TryFrom;
 use super::constants::*;
+use super::cbor::{self, CborDecode, CborEncode, CborError, Decoder};
 
 pub static NETWORK_DEFAULT:Network = Network::Test;
 
@@ -326,6 +327,19 @@ impl FromStr for Address {
     }
 }
 
+// Encoded as a byte string of protocol + payload.
+impl CborEncode for Address {
+    fn encode_cbor(&self, buf: &mut Vec<u8>) {
+        cbor::write_bytes(buf, &self.as_bytes());
+    }
+}
+
+impl CborDecode for Address {
+    fn decode_cbor(decoder: &mut Decoder<'_>) -> Result<Self, CborError> {
+        Address::new_from_bytes(decoder.read_bytes()?).map_err(|e| CborError::InvalidValue(e.to_string()))
+    }
+}
+
 // Implement JSON serialization for Address.
 impl ser::Serialize for Address {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -0,0 +1,318 @@
+//! Canonical DAG-CBOR encoding, byte compatible with the `cbor-gen` encoding of Lotus.
+use cid::{Cid, Codec};
+use num_bigint::{BigInt, Sign};
+use thiserror::Error;
+
+// CBOR major types.
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_TAG: u8 = 6;
+const MAJOR_SIMPLE: u8 = 7;
+
+// The tag of the CIDs of DAG-CBOR.
+const TAG_CID: u64 = 42;
+
+// Simple values.
+const FALSE: u64 = 20;
+const TRUE: u64 = 21;
+
+/// Errors of CBOR decoding.
+#[derive(Debug, Eq, PartialEq, Error)]
+pub enum CborError {
+    #[error("unexpected end of input")]
+    UnexpectedEof,
+    #[error("expected major type {expected}, got {actual}")]
+    UnexpectedType { expected: u8, actual: u8 },
+    #[error("invalid header byte {0:#04x}")]
+    InvalidHeader(u8),
+    #[error("{0} trailing bytes")]
+    TrailingBytes(usize),
+    #[error("invalid value: {0}")]
+    InvalidValue(String),
+}
+
+/// A type that can be encoded to canonical DAG-CBOR.
+pub trait CborEncode {
+    /// Append the encoding of `self` to the buffer.
+    fn encode_cbor(&self, buf: &mut Vec<u8>);
+
+    /// Return the encoding of `self`.
+    fn to_cbor(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode_cbor(&mut buf);
+        buf
+    }
+
+    /// Return the CID (v1, dag-cbor, blake2b-256) of the encoding of `self`.
+    fn cbor_cid(&self) -> Cid {
+        Cid::new_v1(Codec::DagCBOR, multihash::Blake2b256::digest(&self.to_cbor()))
+    }
+}
+
+/// Write the header of a data item, using the shortest form of `value`.
+pub fn write_header(buf: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    if value < 24 {
+        buf.push(major | value as u8);
+    } else if value <= u64::from(u8::MAX) {
+        buf.push(major | 24);
+        buf.push(value as u8);
+    } else if value <= u64::from(u16::MAX) {
+        buf.push(major | 25);
+        buf.extend_from_slice(&(value as u16).to_be_bytes());
+    } else if value <= u64::from(u32::MAX) {
+        buf.push(major | 26);
+        buf.extend_from_slice(&(value as u32).to_be_bytes());
+    } else {
+        buf.push(major | 27);
+        buf.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+/// Write the header of an array with `len` items.
+pub fn write_array_header(buf: &mut Vec<u8>, len: usize) {
+    write_header(buf, MAJOR_ARRAY, len as u64);
+}
+
+pub fn write_u64(buf: &mut Vec<u8>, value: u64) {
+    write_header(buf, MAJOR_UNSIGNED, value);
+}
+
+pub fn write_i64(buf: &mut Vec<u8>, value: i64) {
+    if value >= 0 {
+        write_header(buf, MAJOR_UNSIGNED, value as u64);
+    } else {
+        write_header(buf, MAJOR_NEGATIVE, (-1 - value) as u64);
+    }
+}
+
+pub fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_header(buf, MAJOR_BYTES, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+/// Write a CID as DAG-CBOR does: tag 42 on the bytes of the CID after a zero byte.
+pub fn write_cid(buf: &mut Vec<u8>, cid: &Cid) {
+    write_header(buf, MAJOR_TAG, TAG_CID);
+    let mut bytes = vec![0];
+    bytes.extend_from_slice(&cid.to_bytes());
+    write_bytes(buf, &bytes);
+}
+
+/// Return the Lotus byte form of a big integer: empty for zero,
+/// otherwise a sign byte (0 positive, 1 negative) followed by the big-endian magnitude.
+pub fn bigint_to_bytes(int: &BigInt) -> Vec<u8> {
+    let (sign, magnitude) = int.to_bytes_be();
+    let sign = match sign {
+        Sign::NoSign => return Vec::new(),
+        Sign::Plus => 0,
+        Sign::Minus => 1,
+    };
+    let mut bytes = Vec::with_capacity(1 + magnitude.len());
+    bytes.push(sign);
+    bytes.extend_from_slice(&magnitude);
+    bytes
+}
+
+/// Parse the Lotus byte form of a big integer, see `bigint_to_bytes`.
+pub fn bigint_from_bytes(bytes: &[u8]) -> Result<BigInt, CborError> {
+    match bytes.split_first() {
+        None => Ok(BigInt::from(0)),
+        Some((0, magnitude)) => Ok(BigInt::from_bytes_be(Sign::Plus, magnitude)),
+        Some((1, magnitude)) => Ok(BigInt::from_bytes_be(Sign::Minus, magnitude)),
+        Some((sign, _)) => Err(CborError::InvalidValue(format!("big integer sign byte {}", sign))),
+    }
+}
+
+/// A type that can be decoded from DAG-CBOR.
+pub trait CborDecode: Sized {
+    /// Decode `Self` from the next data item of the decoder.
+    fn decode_cbor(decoder: &mut Decoder<'_>) -> Result<Self, CborError>;
+
+    /// Decode `Self` from `bytes`, which must hold exactly one data item.
+    fn from_cbor(bytes: &[u8]) -> Result<Self, CborError> {
+        let mut decoder = Decoder::new(bytes);
+        let value = Self::decode_cbor(&mut decoder)?;
+        decoder.finish()?;
+        Ok(value)
+    }
+}
+
+/// Reads the data items of a CBOR buffer in order.
+pub struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CborError> {
+        if self.bytes.len() < len {
+            return Err(CborError::UnexpectedEof);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    /// Read the header of the next data item, returning its major type and value.
+    pub fn read_header(&mut self) -> Result<(u8, u64), CborError> {
+        let byte = self.take(1)?[0];
+        let (major, info) = (byte >> 5, byte & 0x1f);
+        let len = match info {
+            0..=23 => return Ok((major, u64::from(info))),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => return Err(CborError::InvalidHeader(byte)),
+        };
+        let value = self.take(len)?.iter().fold(0u64, |value, byte| value << 8 | u64::from(*byte));
+        Ok((major, value))
+    }
+
+    fn read_major(&mut self, expected: u8) -> Result<u64, CborError> {
+        match self.read_header()? {
+            (major, value) if major == expected => Ok(value),
+            (actual, _) => Err(CborError::UnexpectedType { expected, actual }),
+        }
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, CborError> {
+        self.read_major(MAJOR_UNSIGNED)
+    }
+
+    pub fn read_i64(&mut self) -> Result<i64, CborError> {
+        let out_of_range = |value| CborError::InvalidValue(format!("{} overflows i64", value));
+        match self.read_header()? {
+            (MAJOR_UNSIGNED, value) if value <= i64::MAX as u64 => Ok(value as i64),
+            (MAJOR_NEGATIVE, value) if value <= i64::MAX as u64 => Ok(-1 - value as i64),
+            (MAJOR_UNSIGNED, value) | (MAJOR_NEGATIVE, value) => Err(out_of_range(value)),
+            (actual, _) => Err(CborError::UnexpectedType { expected: MAJOR_UNSIGNED, actual }),
+        }
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, CborError> {
+        match self.read_major(MAJOR_SIMPLE)? {
+            FALSE => Ok(false),
+            TRUE => Ok(true),
+            value => Err(CborError::InvalidValue(format!("simple value {} isn't a bool", value))),
+        }
+    }
+
+    pub fn read_bytes(&mut self) -> Result<&'a [u8], CborError> {
+        let len = self.read_major(MAJOR_BYTES)?;
+        self.take(len as usize)
+    }
+
+    /// Read the header of an array, returning its number of items.
+    pub fn read_array_header(&mut self) -> Result<usize, CborError> {
+        Ok(self.read_major(MAJOR_ARRAY)? as usize)
+    }
+
+    /// Read the header of an array which must have `len` items.
+    pub fn read_tuple_header(&mut self, len: usize) -> Result<(), CborError> {
+        match self.read_array_header()? {
+            actual if actual == len => Ok(()),
+            actual => Err(CborError::InvalidValue(format!("expected {} fields, got {}", len, actual))),
+        }
+    }
+
+    /// Check that the whole buffer has been read.
+    pub fn finish(&self) -> Result<(), CborError> {
+        match self.bytes.len() {
+            0 => Ok(()),
+            len => Err(CborError::TrailingBytes(len)),
+        }
+    }
+}
+
+impl CborEncode for BigInt {
+    fn encode_cbor(&self, buf: &mut Vec<u8>) {
+        write_bytes(buf, &bigint_to_bytes(self));
+    }
+}
+
+impl CborDecode for BigInt {
+    fn decode_cbor(decoder: &mut Decoder<'_>) -> Result<Self, CborError> {
+        bigint_from_bytes(decoder.read_bytes()?)
+    }
+}
+
+impl CborDecode for u64 {
+    fn decode_cbor(decoder: &mut Decoder<'_>) -> Result<Self, CborError> {
+        decoder.read_u64()
+    }
+}
+
+impl CborDecode for i64 {
+    fn decode_cbor(decoder: &mut Decoder<'_>) -> Result<Self, CborError> {
+        decoder.read_i64()
+    }
+}
+
+impl CborDecode for bool {
+    fn decode_cbor(decoder: &mut Decoder<'_>) -> Result<Self, CborError> {
+        decoder.read_bool()
+    }
+}
+
+impl CborDecode for Vec<u8> {
+    fn decode_cbor(decoder: &mut Decoder<'_>) -> Result<Self, CborError> {
+        Ok(decoder.read_bytes()?.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_encoding() {
+        let encode = |value: i64| {
+            let mut buf = Vec::new();
+            write_i64(&mut buf, value);
+            buf
+        };
+        assert_eq!(encode(0), vec![0x00]);
+        assert_eq!(encode(23), vec![0x17]);
+        assert_eq!(encode(24), vec![0x18, 0x18]);
+        assert_eq!(encode(256), vec![0x19, 0x01, 0x00]);
+        assert_eq!(encode(65536), vec![0x1a, 0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(encode(1 << 32), vec![0x1b, 0, 0, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(encode(-1), vec![0x20]);
+        assert_eq!(encode(-500), vec![0x39, 0x01, 0xf3]);
+    }
+
+    #[test]
+    fn bigint_encoding() {
+        assert_eq!(BigInt::from(0).to_cbor(), vec![0x40]);
+        assert_eq!(BigInt::from(256).to_cbor(), vec![0x43, 0x00, 0x01, 0x00]);
+        assert_eq!(BigInt::from(-1).to_cbor(), vec![0x42, 0x01, 0x01]);
+        for int in &[0, 256, -1, i64::MIN] {
+            let int = BigInt::from(*int);
+            assert_eq!(BigInt::from_cbor(&int.to_cbor()).unwrap(), int);
+        }
+    }
+
+    #[test]
+    fn decoding() {
+        for value in &[0, 23, 24, 256, 65536, 1 << 32, -1, -500, i64::MIN, i64::MAX] {
+            let mut buf = Vec::new();
+            write_i64(&mut buf, *value);
+            assert_eq!(i64::from_cbor(&buf).unwrap(), *value);
+        }
+        assert_eq!(u64::from_cbor(&[0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).unwrap(), u64::MAX);
+        assert_eq!(bool::from_cbor(&[0xf5]).unwrap(), true);
+        assert_eq!(Vec::<u8>::from_cbor(&[0x42, 0xaa, 0xbb]).unwrap(), vec![0xaa, 0xbb]);
+
+        assert_eq!(u64::from_cbor(&[0x20]), Err(CborError::UnexpectedType { expected: 0, actual: 1 }));
+        assert_eq!(Vec::<u8>::from_cbor(&[0x42, 0xaa]), Err(CborError::UnexpectedEof));
+        assert_eq!(u64::from_cbor(&[0x01, 0x02]), Err(CborError::TrailingBytes(1)));
+        assert_eq!(u64::from_cbor(&[0x1c]), Err(CborError::InvalidHeader(0x1c)));
+    }
+}
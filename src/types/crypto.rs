@@ -3,7 +3,8 @@ use serde_repr::{Serialize_repr, Deserialize_repr};
 use std::convert::TryFrom;
 use thiserror::Error;
 use super::bytes::Bytes;
-use super::address::Protocol;
+use super::address::{Address, Protocol};
+use super::cbor::{self, CborEncode};
 
 #[repr(u8)]
 #[derive(Eq, PartialEq, Debug, Clone, Copy, Hash, Serialize_repr, Deserialize_repr)]
@@ -77,6 +78,55 @@ impl Signature {
             data: Bytes::from(sig)
         }
     }
+
+    pub fn new_bls(sig: Vec<u8>) -> Self {
+        Self {
+            r#type: SignatureType::Bls,
+            data: Bytes::from(sig),
+        }
+    }
+
+    /// Return the signature type.
+    pub fn r#type(&self) -> SignatureType {
+        self.r#type
+    }
+
+    /// Return the signature bytes.
+    pub fn data(&self) -> &[u8] {
+        self.data.as_inner()
+    }
+
+    /// Verify that the data was signed by the key of the address.
+    ///
+    /// The address must be a key address (`Secp256k1` or `BLS` protocol), not an ID address.
+    pub fn verify(&self, data: &[u8], addr: &Address) -> Result<(), CryptoError> {
+        match (self.r#type, addr.protocol()) {
+            (SignatureType::Secp256k1, Protocol::Secp256k1) => {
+                let public_key = crate::wallet::secp256k1::recover_public_key(self.data(), data)?;
+                let recovered = Address::new_secp256k1_addr(&public_key)
+                    .map_err(|_| CryptoError::VerifyFailed)?;
+                if &recovered == addr {
+                    Ok(())
+                } else {
+                    Err(CryptoError::VerifyFailed)
+                }
+            }
+            (SignatureType::Bls, Protocol::Bls) => {
+                crate::wallet::bls::verify(self.data(), addr.payload(), data)
+            }
+            (ty, protocol) => Err(CryptoError::NotSameType(ty, protocol)),
+        }
+    }
+}
+
+// Encoded as a byte string of the type byte followed by the signature bytes.
+impl CborEncode for Signature {
+    fn encode_cbor(&self, buf: &mut Vec<u8>) {
+        let mut bytes = Vec::with_capacity(1 + self.data.as_inner().len());
+        bytes.push(u8::from(self.r#type));
+        bytes.extend_from_slice(self.data.as_inner());
+        cbor::write_bytes(buf, &bytes);
+    }
 }
 #[repr(u8)]
 #[derive(Clone, Debug, Serialize_repr, Deserialize_repr)]
@@ -92,8 +142,11 @@ pub enum DomainSeparationTag {
 
 #[cfg(test)]
 mod tests {
-    use super::Signature;
+    use super::{Signature, CryptoError};
     use crate::types::crypto::{SignatureType, Bytes};
+    use crate::types::address::{Address, Protocol};
+    use crate::types::keystore::KeyType;
+    use crate::wallet::LocalWallet;
     use serde_json;
 
     #[test]
@@ -105,4 +158,32 @@ mod tests {
 
         println!("s {}", serde_json::to_string(&s).unwrap());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn signature_verify() {
+        let mut wallet = LocalWallet::new();
+        let secp = wallet.generate(KeyType::Secp256k1).unwrap();
+        let bls = wallet.generate(KeyType::Bls).unwrap();
+
+        for addr in &[secp.clone(), bls.clone()] {
+            let signature = wallet.sign(addr, b"data").unwrap();
+            assert!(signature.verify(b"data", addr).is_ok());
+            assert_eq!(signature.verify(b"other", addr), Err(CryptoError::VerifyFailed));
+        }
+
+        let signature = wallet.sign(&secp, b"data").unwrap();
+        assert_eq!(signature.r#type(), SignatureType::Secp256k1);
+        assert_eq!(signature.data().len(), 65);
+        assert_eq!(
+            signature.verify(b"data", &bls),
+            Err(CryptoError::NotSameType(SignatureType::Secp256k1, Protocol::Bls))
+        );
+        let other = wallet.generate(KeyType::Secp256k1).unwrap();
+        assert_eq!(signature.verify(b"data", &other), Err(CryptoError::VerifyFailed));
+        let id = Address::new_id_addr(1000).unwrap();
+        assert_eq!(
+            signature.verify(b"data", &id),
+            Err(CryptoError::NotSameType(SignatureType::Secp256k1, Protocol::Id))
+        );
+    }
+}
@@ -1,11 +1,11 @@
 use serde::{Serialize, Deserialize};
-use num_bigint::BigInt;
 use cid::Cid;
 use libp2p_core::PeerId;
-use super::utils::{bigint_json, peerid_json, cid_json};
+use super::utils::{peerid_json, cid_json};
 use super::address::Address;
 use super::header::ChainEpoch;
-use super::piece::UnpaddedPieceSize;
+use super::piece::{UnpaddedPieceSize, PaddedPieceSize};
+use super::token::TokenAmount;
 
 pub type DealId = u64;
 
@@ -23,8 +23,7 @@ pub struct StartDealParams {
     // pub data: storagemarket::DataRef,
     pub wallet: Address,
     pub miner: Address,
-    #[serde(with = "bigint_json")]
-    pub epoch_price: BigInt,
+    pub epoch_price: TokenAmount,
     pub min_blocks_duration: u64,
     pub deal_start_epoch: ChainEpoch,
 }
@@ -50,8 +49,7 @@ pub struct DealInfo {
     #[serde(with = "cid_json")]
     pub piece_cid: Cid,
     pub size: u64,
-    #[serde(with = "bigint_json")]
-    pub price_per_epoch: BigInt,
+    pub price_per_epoch: TokenAmount,
     pub duration: u64,
     #[serde(rename = "DealID")]
     pub deal_id: DealId,
@@ -64,8 +62,7 @@ pub struct QueryOffer {
     #[serde(with = "cid_json")]
     pub root: Cid,
     pub size: u64,
-    #[serde(with = "bigint_json")]
-    pub min_price: BigInt,
+    pub min_price: TokenAmount,
     pub payment_interval: u64,
     pub payment_interval_increase: u64,
     pub miner: Address,
@@ -82,8 +79,7 @@ pub struct RetrievalOrder {
     pub root: Cid,
     pub size: u64,
     // TODO: support offset
-    #[serde(with = "bigint_json")]
-    pub total: BigInt,
+    pub total: TokenAmount,
     pub payment_interval: u64,
     pub payment_interval_increase: u64,
     pub client: Address,
@@ -99,4 +95,50 @@ pub struct CommPRet {
     #[serde(with = "cid_json")]
     pub root: Cid,
     pub size: UnpaddedPieceSize,
-}
\ No newline at end of file
+}
+
+/// The escrow balance of a storage market participant.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MarketBalance {
+    pub escrow: TokenAmount,
+    pub locked: TokenAmount,
+}
+
+/// A storage deal as published on chain.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DealProposal {
+    #[serde(rename = "PieceCID")]
+    #[serde(with = "cid_json")]
+    pub piece_cid: Cid,
+    pub piece_size: PaddedPieceSize,
+    #[serde(default)]
+    pub verified_deal: bool,
+    pub client: Address,
+    pub provider: Address,
+    pub start_epoch: ChainEpoch,
+    pub end_epoch: ChainEpoch,
+    pub storage_price_per_epoch: TokenAmount,
+    pub provider_collateral: TokenAmount,
+    pub client_collateral: TokenAmount,
+}
+
+/// The on-chain state of a storage deal.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DealState {
+    /// -1 if not yet included in proven sector
+    pub sector_start_epoch: ChainEpoch,
+    /// -1 if deal state never updated
+    pub last_updated_epoch: ChainEpoch,
+    /// -1 if deal never slashed
+    pub slash_epoch: ChainEpoch,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MarketDeal {
+    pub proposal: DealProposal,
+    pub state: DealState,
+}
@@ -0,0 +1,228 @@
+use std::fmt;
+use serde::{de, ser};
+use super::cbor::{self, CborDecode, CborEncode, CborError, Decoder};
+
+/// The exit code of applying a message, either one of the common codes of the VM and
+/// the builtin actors, or a code specific to an actor.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ExitCode {
+    Ok,
+    // System errors, raised by the VM.
+    SysErrSenderInvalid,
+    SysErrSenderStateInvalid,
+    SysErrInvalidMethod,
+    SysErrInvalidParameters,
+    SysErrInvalidReceiver,
+    SysErrInsufficientFunds,
+    SysErrOutOfGas,
+    SysErrForbidden,
+    SysErrIllegalActor,
+    SysErrIllegalArgument,
+    SysErrSerialization,
+    // Common errors of the builtin actors.
+    ErrIllegalArgument,
+    ErrNotFound,
+    ErrForbidden,
+    ErrInsufficientFunds,
+    ErrIllegalState,
+    ErrSerialization,
+    /// Any other code, e.g. the ones specific to an actor from 32.
+    Other(i64),
+}
+
+impl ExitCode {
+    /// The first code specific to an actor.
+    pub const FIRST_ACTOR_SPECIFIC: i64 = 32;
+
+    pub fn value(self) -> i64 {
+        match self {
+            ExitCode::Ok => 0,
+            ExitCode::SysErrSenderInvalid => 1,
+            ExitCode::SysErrSenderStateInvalid => 2,
+            ExitCode::SysErrInvalidMethod => 3,
+            ExitCode::SysErrInvalidParameters => 4,
+            ExitCode::SysErrInvalidReceiver => 5,
+            ExitCode::SysErrInsufficientFunds => 6,
+            ExitCode::SysErrOutOfGas => 7,
+            ExitCode::SysErrForbidden => 8,
+            ExitCode::SysErrIllegalActor => 9,
+            ExitCode::SysErrIllegalArgument => 10,
+            ExitCode::SysErrSerialization => 11,
+            ExitCode::ErrIllegalArgument => 16,
+            ExitCode::ErrNotFound => 17,
+            ExitCode::ErrForbidden => 18,
+            ExitCode::ErrInsufficientFunds => 19,
+            ExitCode::ErrIllegalState => 20,
+            ExitCode::ErrSerialization => 21,
+            ExitCode::Other(code) => code,
+        }
+    }
+
+    pub fn is_success(self) -> bool {
+        self == ExitCode::Ok
+    }
+
+    /// Whether the message failed in the VM, before or outside of the code of the actor.
+    pub fn is_system_error(self) -> bool {
+        (1..16).contains(&self.value())
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ExitCode::Ok => "Ok",
+            ExitCode::SysErrSenderInvalid => "SysErrSenderInvalid",
+            ExitCode::SysErrSenderStateInvalid => "SysErrSenderStateInvalid",
+            ExitCode::SysErrInvalidMethod => "SysErrInvalidMethod",
+            ExitCode::SysErrInvalidParameters => "SysErrInvalidParameters",
+            ExitCode::SysErrInvalidReceiver => "SysErrInvalidReceiver",
+            ExitCode::SysErrInsufficientFunds => "SysErrInsufficientFunds",
+            ExitCode::SysErrOutOfGas => "SysErrOutOfGas",
+            ExitCode::SysErrForbidden => "SysErrForbidden",
+            ExitCode::SysErrIllegalActor => "SysErrIllegalActor",
+            ExitCode::SysErrIllegalArgument => "SysErrIllegalArgument",
+            ExitCode::SysErrSerialization => "SysErrSerialization",
+            ExitCode::ErrIllegalArgument => "ErrIllegalArgument",
+            ExitCode::ErrNotFound => "ErrNotFound",
+            ExitCode::ErrForbidden => "ErrForbidden",
+            ExitCode::ErrInsufficientFunds => "ErrInsufficientFunds",
+            ExitCode::ErrIllegalState => "ErrIllegalState",
+            ExitCode::ErrSerialization => "ErrSerialization",
+            ExitCode::Other(_) => "ExitCode",
+        }
+    }
+
+    /// Short description of the common codes.
+    pub fn description(self) -> Option<&'static str> {
+        Some(match self {
+            ExitCode::Ok => "success",
+            ExitCode::SysErrSenderInvalid => "sender doesn't exist or isn't an account actor",
+            ExitCode::SysErrSenderStateInvalid => "sender nonce or balance doesn't match the message",
+            ExitCode::SysErrInvalidMethod => "receiver has no such method",
+            ExitCode::SysErrInvalidParameters => "params can't be decoded",
+            ExitCode::SysErrInvalidReceiver => "receiver doesn't exist and can't be created",
+            ExitCode::SysErrInsufficientFunds => "sender balance doesn't cover the value",
+            ExitCode::SysErrOutOfGas => "out of gas",
+            ExitCode::SysErrForbidden => "caller isn't allowed to call the method",
+            ExitCode::SysErrIllegalActor => "actor performed an illegal operation",
+            ExitCode::SysErrIllegalArgument => "actor passed an illegal argument to the VM",
+            ExitCode::SysErrSerialization => "VM failed to (de)serialize a value",
+            ExitCode::ErrIllegalArgument => "illegal argument",
+            ExitCode::ErrNotFound => "not found",
+            ExitCode::ErrForbidden => "forbidden",
+            ExitCode::ErrInsufficientFunds => "insufficient funds",
+            ExitCode::ErrIllegalState => "illegal actor state",
+            ExitCode::ErrSerialization => "actor failed to (de)serialize a value",
+            ExitCode::Other(_) => return None,
+        })
+    }
+}
+
+impl From<i64> for ExitCode {
+    fn from(code: i64) -> Self {
+        match code {
+            0 => ExitCode::Ok,
+            1 => ExitCode::SysErrSenderInvalid,
+            2 => ExitCode::SysErrSenderStateInvalid,
+            3 => ExitCode::SysErrInvalidMethod,
+            4 => ExitCode::SysErrInvalidParameters,
+            5 => ExitCode::SysErrInvalidReceiver,
+            6 => ExitCode::SysErrInsufficientFunds,
+            7 => ExitCode::SysErrOutOfGas,
+            8 => ExitCode::SysErrForbidden,
+            9 => ExitCode::SysErrIllegalActor,
+            10 => ExitCode::SysErrIllegalArgument,
+            11 => ExitCode::SysErrSerialization,
+            16 => ExitCode::ErrIllegalArgument,
+            17 => ExitCode::ErrNotFound,
+            18 => ExitCode::ErrForbidden,
+            19 => ExitCode::ErrInsufficientFunds,
+            20 => ExitCode::ErrIllegalState,
+            21 => ExitCode::ErrSerialization,
+            code => ExitCode::Other(code),
+        }
+    }
+}
+
+impl From<ExitCode> for i64 {
+    fn from(code: ExitCode) -> Self {
+        code.value()
+    }
+}
+
+impl Default for ExitCode {
+    fn default() -> Self {
+        ExitCode::Ok
+    }
+}
+
+impl fmt::Debug for ExitCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExitCode::Other(code) => write!(f, "ExitCode({})", code),
+            _ => write!(f, "{}({})", self.name(), self.value()),
+        }
+    }
+}
+
+impl fmt::Display for ExitCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.description() {
+            Some(description) => write!(f, "{:?}: {}", self, description),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+// Implement JSON serialization for ExitCode.
+impl ser::Serialize for ExitCode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: ser::Serializer,
+    {
+        self.value().serialize(serializer)
+    }
+}
+
+// Implement JSON deserialization for ExitCode.
+impl<'de> de::Deserialize<'de> for ExitCode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: de::Deserializer<'de>,
+    {
+        Ok(i64::deserialize(deserializer)?.into())
+    }
+}
+
+impl CborEncode for ExitCode {
+    fn encode_cbor(&self, buf: &mut Vec<u8>) {
+        cbor::write_i64(buf, self.value());
+    }
+}
+
+impl CborDecode for ExitCode {
+    fn decode_cbor(decoder: &mut Decoder<'_>) -> Result<Self, CborError> {
+        Ok(decoder.read_i64()?.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_code() {
+        for code in 0..40 {
+            assert_eq!(ExitCode::from(code).value(), code);
+        }
+        assert!(ExitCode::Ok.is_success());
+        assert!(ExitCode::SysErrOutOfGas.is_system_error());
+        assert!(!ExitCode::ErrNotFound.is_system_error());
+        assert_eq!(ExitCode::from(33), ExitCode::Other(33));
+        assert_eq!(ExitCode::SysErrOutOfGas.to_string(), "SysErrOutOfGas(7): out of gas");
+        assert_eq!(ExitCode::Other(33).to_string(), "ExitCode(33)");
+
+        assert_eq!(serde_json::from_str::<ExitCode>("6").unwrap(), ExitCode::SysErrInsufficientFunds);
+        assert_eq!(serde_json::to_string(&ExitCode::ErrIllegalState).unwrap(), "20");
+        assert_eq!(ExitCode::from_cbor(&ExitCode::Other(1000).to_cbor()).unwrap(), ExitCode::Other(1000));
+    }
+}
@@ -5,9 +5,13 @@ use num_bigint::BigInt;
 use super::address::Address;
 use super::ticket::Ticket;
 use super::proofs::{ElectionProof, PoStProof};
-use super::crypto::Signature;
+use super::crypto::{Signature, SignatureType, CryptoError};
+use super::message::UnsignedMessage;
+use super::address::Protocol;
+use super::cbor::{self, CborEncode};
 use super::utils::{vec_cid_json, cid_json, bytes_json, bigint_json};
 use super::tipset::TipSet;
+use super::token::TokenAmount;
 
 pub type ChainEpoch = i64;
 
@@ -62,6 +66,9 @@ pub struct BlockHeader {
     pub block_sig: Signature,
     ///
     pub fork_signaling: u64,
+    /// The base fee of the parent tipset, since Lotus v0.5, `None` before.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_base_fee: Option<TokenAmount>,
     /*
     /// internal
     #[serde(skip)]
@@ -69,6 +76,74 @@ pub struct BlockHeader {
     */
 }
 
+impl CborEncode for BeaconEntry {
+    fn encode_cbor(&self, buf: &mut Vec<u8>) {
+        cbor::write_array_header(buf, 2);
+        cbor::write_u64(buf, self.round);
+        cbor::write_bytes(buf, &self.data);
+    }
+}
+
+impl BlockHeader {
+    /// Return the CID of the block, the same as Lotus computes.
+    pub fn cid(&self) -> Cid {
+        self.cbor_cid()
+    }
+
+    /// Verify `bls_aggregate` against the BLS messages of the block, in block order.
+    ///
+    /// `senders[i]` is the BLS address of the sender of `messages[i]`, as `from` may be
+    /// an ID address which must be resolved first, e.g. with `StateApi::state_account_key`.
+    pub fn verify_bls_aggregate(&self, messages: &[UnsignedMessage], senders: &[Address]) -> Result<(), CryptoError> {
+        if self.bls_aggregate.r#type() != SignatureType::Bls {
+            return Err(CryptoError::Bls("aggregate is not a BLS signature".into()));
+        }
+        if let Some(sender) = senders.iter().find(|sender| sender.protocol() != Protocol::Bls) {
+            return Err(CryptoError::NotSameType(SignatureType::Bls, sender.protocol()));
+        }
+
+        let cids = messages.iter().map(|msg| msg.cid().to_bytes()).collect::<Vec<_>>();
+        let data = cids.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let public_keys = senders.iter().map(Address::payload).collect::<Vec<_>>();
+        crate::wallet::bls::verify_aggregate(self.bls_aggregate.data(), &public_keys, &data)
+    }
+}
+
+// The headers of nodes older than Lotus v0.5 are the 15 fields before the parent base fee.
+impl CborEncode for BlockHeader {
+    fn encode_cbor(&self, buf: &mut Vec<u8>) {
+        cbor::write_array_header(buf, if self.parent_base_fee.is_some() { 16 } else { 15 });
+        self.miner.encode_cbor(buf);
+        self.ticket.encode_cbor(buf);
+        self.election_proof.encode_cbor(buf);
+        let beacon_entries = self.beacon_entries.as_deref().unwrap_or_default();
+        cbor::write_array_header(buf, beacon_entries.len());
+        for entry in beacon_entries {
+            entry.encode_cbor(buf);
+        }
+        cbor::write_array_header(buf, self.win_post_proof.len());
+        for proof in &self.win_post_proof {
+            proof.encode_cbor(buf);
+        }
+        cbor::write_array_header(buf, self.parents.len());
+        for parent in &self.parents {
+            cbor::write_cid(buf, parent);
+        }
+        self.parent_weight.encode_cbor(buf);
+        cbor::write_i64(buf, self.height);
+        cbor::write_cid(buf, &self.parent_state_root);
+        cbor::write_cid(buf, &self.parent_message_receipts);
+        cbor::write_cid(buf, &self.messages);
+        self.bls_aggregate.encode_cbor(buf);
+        cbor::write_u64(buf, self.timestamp);
+        self.block_sig.encode_cbor(buf);
+        cbor::write_u64(buf, self.fork_signaling);
+        if let Some(parent_base_fee) = &self.parent_base_fee {
+            parent_base_fee.encode_cbor(buf);
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum HeadChangeType {
@@ -84,15 +159,58 @@ pub struct HeadChange {
     pub val: TipSet,
 }
 
+/// A block of the chain of a node before Lotus v0.5, with its CID in `tests`.
+#[cfg(test)]
+pub(crate) const BLOCK: &str = r#"{"Miner":"t01000","Ticket":{"VRFProof":"k4aywRis+mYWN56o3OQOAxEFxKSp777TR1h8hcTEeWlLwvERi2oXnTE7xzS0uoLICnEhoGs9BL5MGDYpf3dfmvLD+h7iBimSpl6rY7bysDbuKreKXa9GwAPN3fQqJB1O"},"ElectionProof":{"VRFProof":"g7Ki1qDQtj0Q1o2bRpHZqD++UqFfjPaOJ5WYT2wJjCgGxg/+2L4cozSU/F7IzGIfE1E79C0brMGROGCLMui4qiSZr1D9sJmn+EBwrLjbqpiJEVXqoXoFEkw7/xpFjIat"},"BeaconEntries":null,"WinPoStProof":[{"RegisteredProof":9,"ProofBytes":"scKG734ZjZjlLv1I9z/7R4qmL3M0kpkTKtBa00pGVxA8cd3myhwhocX8BL4pHl8QmMbkPqp5iXh0sbCdJjbJ6/OmAvpATiAYf3R7pTMOdkLvxFofq4NDEtv8t/I4fnOJAcTvG0ozeNA3MM0KjR2X+kfz4Fo4kVflCdhcT9cKlYBO7IiVKYm/RN0zyvJi6pzhmBtryhGzYyNYv3jWVde8qUtIQnD0169SzYVrbZlfF4ydpgGj5PriYRXrCTi9DXmz"}],"Parents":[{"/":"bafy2bzacebwut2il7udv5d3yzscpwbomvj5ocq6lkxh4kcusiy5juesvpun4c"}],"ParentWeight":"629642112","Height":149063,"ParentStateRoot":{"/":"bafy2bzaceae7pqh2wupmp3fqnlbsxx2czjku5rbisl3qdtaa5mehs2hkjak3a"},"ParentMessageReceipts":{"/":"bafy2bzaceaa43et73tgxsoh2xizd4mxhbrcfig4kqp25zfa5scdgkzppllyuu"},"Messages":{"/":"bafy2bzacecgw6dqj4bctnbnyqfujltkwu7xc7ttaaato4i5miroxr4bayhfea"},"BLSAggregate":{"Type":2,"Data":"wAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"},"Timestamp":1592693392,"BlockSig":{"Type":2,"Data":"l+3ZTa9Q1mj8UcVMAetZSuZphQQJUDfaSbXbZf6rNTBhrqE7feLMcTCCMcUOClNnFH+P8HQmOZ8YwH47vU2vw6maLU33bS5Bc6+MvF7gjFx2pRHgq5GM8SPunDA3fKFe"},"ForkSignaling":0}"#;
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn block_header_json() {
-        let json = r#"{"Miner":"t01000","Ticket":{"VRFProof":"k4aywRis+mYWN56o3OQOAxEFxKSp777TR1h8hcTEeWlLwvERi2oXnTE7xzS0uoLICnEhoGs9BL5MGDYpf3dfmvLD+h7iBimSpl6rY7bysDbuKreKXa9GwAPN3fQqJB1O"},"ElectionProof":{"VRFProof":"g7Ki1qDQtj0Q1o2bRpHZqD++UqFfjPaOJ5WYT2wJjCgGxg/+2L4cozSU/F7IzGIfE1E79C0brMGROGCLMui4qiSZr1D9sJmn+EBwrLjbqpiJEVXqoXoFEkw7/xpFjIat"},"BeaconEntries":null,"WinPoStProof":[{"RegisteredProof":9,"ProofBytes":"scKG734ZjZjlLv1I9z/7R4qmL3M0kpkTKtBa00pGVxA8cd3myhwhocX8BL4pHl8QmMbkPqp5iXh0sbCdJjbJ6/OmAvpATiAYf3R7pTMOdkLvxFofq4NDEtv8t/I4fnOJAcTvG0ozeNA3MM0KjR2X+kfz4Fo4kVflCdhcT9cKlYBO7IiVKYm/RN0zyvJi6pzhmBtryhGzYyNYv3jWVde8qUtIQnD0169SzYVrbZlfF4ydpgGj5PriYRXrCTi9DXmz"}],"Parents":[{"/":"bafy2bzacebwut2il7udv5d3yzscpwbomvj5ocq6lkxh4kcusiy5juesvpun4c"}],"ParentWeight":"629642112","Height":149063,"ParentStateRoot":{"/":"bafy2bzaceae7pqh2wupmp3fqnlbsxx2czjku5rbisl3qdtaa5mehs2hkjak3a"},"ParentMessageReceipts":{"/":"bafy2bzaceaa43et73tgxsoh2xizd4mxhbrcfig4kqp25zfa5scdgkzppllyuu"},"Messages":{"/":"bafy2bzacecgw6dqj4bctnbnyqfujltkwu7xc7ttaaato4i5miroxr4bayhfea"},"BLSAggregate":{"Type":2,"Data":"wAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"},"Timestamp":1592693392,"BlockSig":{"Type":2,"Data":"l+3ZTa9Q1mj8UcVMAetZSuZphQQJUDfaSbXbZf6rNTBhrqE7feLMcTCCMcUOClNnFH+P8HQmOZ8YwH47vU2vw6maLU33bS5Bc6+MvF7gjFx2pRHgq5GM8SPunDA3fKFe"},"ForkSignaling":0}"#;
-
-        let block_header = serde_json::from_str::<BlockHeader>(json);
+        let block_header = serde_json::from_str::<BlockHeader>(BLOCK);
         println!("{:?}", block_header);
     }
+
+    #[test]
+    fn empty_bls_aggregate() {
+        let block_header = serde_json::from_str::<BlockHeader>(BLOCK).unwrap();
+        // the block has no BLS messages
+        assert!(block_header.verify_bls_aggregate(&[], &[]).is_ok());
+    }
+
+    #[test]
+    fn block_header_cid() {
+        let block_header = serde_json::from_str::<BlockHeader>(BLOCK).unwrap();
+        // The CID given by the node for the block.
+        assert_eq!(block_header.cid().to_string(), "bafy2bzacec43cadndvrgpiq3lia65pbyj2t32jltlqp2oszlvknwn5wx3vyii");
+    }
+
+    #[test]
+    fn block_header_base_fee() {
+        let mut json = serde_json::from_str::<serde_json::Value>(BLOCK).unwrap();
+        json["ParentBaseFee"] = "100".into();
+        json["ElectionProof"]["WinCount"] = 1.into();
+        let block_header = serde_json::from_value::<BlockHeader>(json.clone()).unwrap();
+        assert_eq!(block_header.parent_base_fee, Some(TokenAmount::from_atto(100)));
+        assert_eq!(block_header.election_proof.win_count, Some(1));
+        assert_eq!(serde_json::to_value(&block_header).unwrap(), json);
+
+        // The 16 fields of Lotus v0.5, with the win count before the VRF proof.
+        let mut legacy = block_header.clone();
+        legacy.parent_base_fee = None;
+        legacy.election_proof.win_count = None;
+        let (legacy, legacy_proof) = (legacy.to_cbor(), legacy.election_proof.to_cbor());
+        let at = legacy.windows(legacy_proof.len()).position(|bytes| bytes == &legacy_proof[..]).unwrap();
+        let mut expected = [
+            &[0x90][..],
+            &legacy[1..at],
+            &block_header.election_proof.to_cbor(),
+            &legacy[at + legacy_proof.len()..],
+        ].concat();
+        expected.extend(TokenAmount::from_atto(100).to_cbor());
+        assert_eq!(block_header.to_cbor(), expected);
+        assert_eq!(&block_header.election_proof.to_cbor()[..2], &[0x82, 0x01]);
+    }
 }
\ No newline at end of file
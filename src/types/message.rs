@@ -1,28 +1,39 @@
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 pub use num_bigint::{BigInt};
 use cid::Cid;
 use super::utils::{bigint_json, bytes_json, vec_cid_json, cid_json};
-use super::crypto::Signature;
-use super::address::{Address,Protocol};
+use super::crypto::{Signature, SignatureType, CryptoError};
+use super::address::Address;
 use super::bytes::Bytes;
+use super::cbor::{self, CborDecode, CborEncode, CborError};
+use super::exit_code::ExitCode;
+use super::token::TokenAmount;
 
+#[deprecated(note = "messages are encoded natively, use `UnsignedMessage`")]
 pub use plum_message::UnsignedMessage as originUnsignedMessage;
+#[deprecated(note = "use `types::Address`")]
 pub use plum_address::Address as originAddress;
+#[deprecated(note = "use `types::address::Protocol`")]
 pub use plum_address::Protocol as originProtocol;
+#[deprecated(note = "use `TokenAmount` or `num_bigint::BigInt`")]
 pub use plum_bigint::{BigInt as originBigint, bigint_json as originbigint_json};
+#[deprecated(note = "use `types::Bytes`")]
 pub use plum_bytes::Bytes as originBytes;
+#[deprecated(note = "use `lotus_api::ToPrimitive`")]
 pub use num_traits::cast::ToPrimitive;
-use std::convert::TryFrom;
 
+#[deprecated(note = "messages use `types::Address`, which needs no conversion")]
 pub trait AddressConver {
     fn to_api_address(self) -> Address;
 }
 
+#[allow(deprecated)]
 impl AddressConver for originAddress {
      fn to_api_address(self) -> Address{
         let p  = self.protocol() as u8;
         Address::new(
-             Protocol::try_from(p).unwrap() ,
+             super::address::Protocol::try_from(p).unwrap() ,
              self.payload().to_vec(),
         ).unwrap()
     }
@@ -39,26 +50,32 @@ pub struct SignedMessage {
 }
 
 /// The unsigned message.
+///
+/// The gas is priced by `gas_fee_cap` and `gas_premium` like Lotus since v0.5. The messages
+/// of older nodes, with a single `GasPrice`, keep it in `gas_price`: both fields are set to
+/// the price too.
 #[derive(Eq, PartialEq, Clone, Debug, Hash, Serialize, Deserialize)]
-#[serde(rename_all = "PascalCase")]
+#[serde(rename_all = "PascalCase", from = "UnsignedMessageJson", into = "UnsignedMessageJson")]
 pub struct UnsignedMessage {
     ///
     pub version: i64,
     /// The receiver of the unsigned message.
-    pub to: originAddress,
+    pub to: Address,
     /// The sender of the unsigned message.
-    pub from: originAddress,
+    pub from: Address,
     /// The nonce.
     pub nonce: u64,
     /// The value.
-    #[serde(with = "bigint_json")]
-    pub value: BigInt,
-    /// The price of gas.
-    #[serde(with = "bigint_json")]
-    pub gas_price: BigInt,
+    pub value: TokenAmount,
+    /// The single price of gas of the messages of nodes older than Lotus v0.5, which keep
+    /// their shape in JSON and CBOR, `None` for the current messages.
+    pub gas_price: Option<TokenAmount>,
     /// The limit of gas.
-   // #[serde(with = "bigint_json")]
-    pub gas_limit: u64,
+    pub gas_limit: i64,
+    /// The maximum price of gas, including the base fee burnt by the network.
+    pub gas_fee_cap: TokenAmount,
+    /// The price of gas paid to the miner on top of the base fee.
+    pub gas_premium: TokenAmount,
     /// The method.
     pub method: u64,
     /// The params of method.
@@ -66,12 +83,81 @@ pub struct UnsignedMessage {
     pub params: Bytes,
 }
 
+// Either the current shape of the message, or the one with `GasPrice` of older nodes.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct UnsignedMessageJson {
+    version: i64,
+    to: Address,
+    from: Address,
+    nonce: u64,
+    value: TokenAmount,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gas_price: Option<TokenAmount>,
+    gas_limit: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gas_fee_cap: Option<TokenAmount>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gas_premium: Option<TokenAmount>,
+    method: u64,
+    params: Bytes,
+}
+
+impl From<UnsignedMessageJson> for UnsignedMessage {
+    fn from(json: UnsignedMessageJson) -> Self {
+        let price = json.gas_price.clone().unwrap_or_default();
+        Self {
+            version: json.version,
+            to: json.to,
+            from: json.from,
+            nonce: json.nonce,
+            value: json.value,
+            gas_price: json.gas_price,
+            gas_limit: json.gas_limit,
+            gas_fee_cap: json.gas_fee_cap.unwrap_or_else(|| price.clone()),
+            gas_premium: json.gas_premium.unwrap_or(price),
+            method: json.method,
+            params: json.params,
+        }
+    }
+}
+
+impl From<UnsignedMessage> for UnsignedMessageJson {
+    fn from(msg: UnsignedMessage) -> Self {
+        let (gas_fee_cap, gas_premium) = match msg.gas_price {
+            Some(_) => (None, None),
+            None => (Some(msg.gas_fee_cap), Some(msg.gas_premium)),
+        };
+        Self {
+            version: msg.version,
+            to: msg.to,
+            from: msg.from,
+            nonce: msg.nonce,
+            value: msg.value,
+            gas_price: msg.gas_price,
+            gas_limit: msg.gas_limit,
+            gas_fee_cap,
+            gas_premium,
+            method: msg.method,
+            params: msg.params,
+        }
+    }
+}
+
+/// The spec of sending a message.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MessageSendSpec {
+    /// The maximum fee of the message, zero for the default of the node.
+    pub max_fee: TokenAmount,
+}
+
 /// The receipt of applying message.
 #[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct MessageReceipt {
     /// The exit code of VM.
-    pub exit_code: u8,
+    pub exit_code: ExitCode,
     /// The return bytes.
     #[serde(with = "bytes_json")]
     pub r#return: Vec<u8>,
@@ -105,31 +191,184 @@ pub struct ObjStat {
 }
 
 impl UnsignedMessage {
-    pub fn into_origin(&self) -> originUnsignedMessage {
-        originUnsignedMessage{
-            version: self.version,
-            to:  self.to.clone(),
-            from: self.from.clone(),
-            nonce: self.nonce,
-            value: originBigint::from(self.value.to_u128().unwrap() as u128),
-            gas_price: originBigint::from(self.gas_price.to_u128().unwrap() as u128),
-            gas_limit: originBigint::from(self.gas_limit as u128),
-            method:  self.method,
-            params: self.params.clone().into_inner(),
+    /// Return the CID of the message, the same as Lotus computes.
+    pub fn cid(&self) -> Cid {
+        self.cbor_cid()
+    }
+
+    /// The fee of the message if it uses all of its gas limit at the fee cap.
+    pub fn required_funds(&self) -> TokenAmount {
+        TokenAmount::from_atto(self.gas_fee_cap.atto() * self.gas_limit)
+    }
+}
+
+// The messages of older nodes are the 9 fields with the gas price before the gas limit.
+impl CborEncode for UnsignedMessage {
+    fn encode_cbor(&self, buf: &mut Vec<u8>) {
+        cbor::write_array_header(buf, if self.gas_price.is_some() { 9 } else { 10 });
+        cbor::write_i64(buf, self.version);
+        self.to.encode_cbor(buf);
+        self.from.encode_cbor(buf);
+        cbor::write_u64(buf, self.nonce);
+        self.value.encode_cbor(buf);
+        match &self.gas_price {
+            Some(gas_price) => {
+                gas_price.encode_cbor(buf);
+                cbor::write_i64(buf, self.gas_limit);
+            }
+            None => {
+                cbor::write_i64(buf, self.gas_limit);
+                self.gas_fee_cap.encode_cbor(buf);
+                self.gas_premium.encode_cbor(buf);
+            }
         }
+        cbor::write_u64(buf, self.method);
+        cbor::write_bytes(buf, self.params.as_inner());
     }
+}
 
+impl MessageReceipt {
+    pub fn is_success(&self) -> bool {
+        self.exit_code.is_success()
+    }
+
+    /// Decode the return bytes, e.g. into an `ExecReturn` for `InitActor.Exec`.
+    pub fn decode_return<T: CborDecode>(&self) -> Result<T, CborError> {
+        T::from_cbor(&self.r#return)
+    }
+}
+
+impl SignedMessage {
+    /// Return the CID of the message, the same as Lotus computes.
+    ///
+    /// BLS signatures are aggregated in the block, so BLS signed messages
+    /// are identified by the CID of the unsigned message.
     pub fn cid(&self) -> Cid {
-        self.into_origin().cid()
+        match self.signature.r#type() {
+            SignatureType::Bls => self.message.cid(),
+            SignatureType::Secp256k1 => self.cbor_cid(),
+        }
     }
 
+    /// Verify the signature against the sender of the message.
+    ///
+    /// The sender must be a key address, ID addresses have to be resolved first.
+    pub fn verify(&self) -> Result<(), CryptoError> {
+        self.signature.verify(&self.message.cid().to_bytes(), &self.message.from)
+    }
+}
+
+impl CborEncode for SignedMessage {
+    fn encode_cbor(&self, buf: &mut Vec<u8>) {
+        cbor::write_array_header(buf, 2);
+        self.message.encode_cbor(buf);
+        self.signature.encode_cbor(buf);
+    }
+}
+
+/// A message from `from` to `t01`, with its encodings in `tests`.
+#[cfg(test)]
+pub(crate) fn message(from: Address) -> UnsignedMessage {
+    UnsignedMessage {
+        version: 0,
+        to: Address::new_id_addr(1).unwrap(),
+        from,
+        nonce: 5,
+        value: TokenAmount::from_atto(-1),
+        gas_price: None,
+        gas_limit: 1000,
+        gas_fee_cap: TokenAmount::from_atto(256),
+        gas_premium: TokenAmount::zero(),
+        method: 2,
+        params: Bytes::from(vec![0xaa]),
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use cid::Codec;
+
+    fn from() -> Address {
+        Address::new_id_addr(1000).unwrap()
+    }
 
     #[test]
-    fn message_receipt_json_test() {
+    fn unsigned_message_cbor() {
+        let msg = message(from());
+        assert_eq!(
+            msg.to_cbor(),
+            vec![
+                0x8a, // array(10)
+                0x00, // version
+                0x42, 0x00, 0x01, // to
+                0x43, 0x00, 0xe8, 0x07, // from
+                0x05, // nonce
+                0x42, 0x01, 0x01, // value
+                0x19, 0x03, 0xe8, // gas limit
+                0x43, 0x00, 0x01, 0x00, // gas fee cap
+                0x40, // gas premium
+                0x02, // method
+                0x41, 0xaa, // params
+            ]
+        );
 
+        let cid = msg.cid();
+        assert_eq!(cid.codec(), Codec::DagCBOR);
+        assert_eq!(cid, Cid::new_v1(Codec::DagCBOR, multihash::Blake2b256::digest(&msg.to_cbor())));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn unsigned_message_json() {
+        let json = r#"{"Version":0,"To":"t01","From":"t01000","Nonce":5,"Value":"-1","GasLimit":1000,"GasFeeCap":"256","GasPremium":"0","Method":2,"Params":"qg=="}"#;
+        let msg = serde_json::from_str::<UnsignedMessage>(json).unwrap();
+        assert_eq!(msg, message(from()));
+        assert_eq!(serde_json::to_string(&msg).unwrap(), json);
+        assert_eq!(msg.required_funds(), TokenAmount::from_atto(256_000));
+
+        // The shape of older nodes.
+        let json = r#"{"Version":0,"To":"t01","From":"t01000","Nonce":5,"Value":"-1","GasPrice":"256","GasLimit":1000,"Method":2,"Params":"qg=="}"#;
+        let legacy = serde_json::from_str::<UnsignedMessage>(json).unwrap();
+        assert_eq!(legacy.gas_price, Some(TokenAmount::from_atto(256)));
+        assert_eq!(legacy.gas_fee_cap, TokenAmount::from_atto(256));
+        assert_eq!(legacy.gas_premium, TokenAmount::from_atto(256));
+        assert_eq!(legacy.gas_limit, 1000);
+        assert_eq!(serde_json::to_string(&legacy).unwrap(), json);
+        assert_eq!(
+            legacy.to_cbor(),
+            vec![
+                0x89, // array(9)
+                0x00, // version
+                0x42, 0x00, 0x01, // to
+                0x43, 0x00, 0xe8, 0x07, // from
+                0x05, // nonce
+                0x42, 0x01, 0x01, // value
+                0x43, 0x00, 0x01, 0x00, // gas price
+                0x19, 0x03, 0xe8, // gas limit
+                0x02, // method
+                0x41, 0xaa, // params
+            ]
+        );
+    }
+
+    #[test]
+    fn signed_message_cid() {
+        let msg = message(from());
+        let bls = SignedMessage {
+            message: msg.clone(),
+            signature: Signature::new_bls(vec![0; 96]),
+        };
+        assert_eq!(bls.cid(), msg.cid());
+
+        let secp = SignedMessage {
+            message: msg.clone(),
+            signature: Signature::new_secp(vec![1; 65]),
+        };
+        let mut expected = vec![0x82];
+        expected.extend(msg.to_cbor());
+        expected.extend(&[0x58, 66, 1]);
+        expected.extend(vec![1; 65]);
+        assert_eq!(secp.to_cbor(), expected);
+        assert_ne!(secp.cid(), msg.cid());
+    }
+}
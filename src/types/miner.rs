@@ -6,8 +6,9 @@ use super::ticket::Ticket;
 use super::proofs::{ElectionProof, PoStProof};
 use super::message::SignedMessage;
 use super::tipset::TipSetKey;
-use super::sector::{SectorSize, SectorInfo};
-use super::utils::bigint_json;
+use super::sector::{SectorSize, SectorInfo, StoragePower};
+use super::utils::{bigint_json, peerid_json::PeerIdWrapper};
+use super::bytes::Bytes;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -37,3 +38,42 @@ pub struct BlockTemplate {
     #[serde(rename = "WinningPoStProof")]
     pub winning_post_proof: Vec<PoStProof>,
 }
+
+/// The static information of a miner actor.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MinerInfo {
+    /// Account that owns this miner.
+    pub owner: Address,
+    /// Worker account for this miner, which signs blocks and messages.
+    pub worker: Address,
+    /// Libp2p identity that should be used when connecting to this miner.
+    pub peer_id: Option<PeerIdWrapper>,
+    /// Libp2p multiaddresses used to establish a connection with this miner.
+    pub multiaddrs: Option<Vec<Bytes>>,
+    /// Amount of space in each sector committed to the network by this miner.
+    pub sector_size: SectorSize,
+    /// The number of sectors in each Window PoSt partition (proof).
+    #[serde(default)]
+    pub window_po_st_partition_sectors: u64,
+}
+
+/// The power claimed by a miner or the whole network.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Claim {
+    /// Sum of raw byte power.
+    #[serde(with = "bigint_json")]
+    pub raw_byte_power: StoragePower,
+    /// Sum of quality adjusted power.
+    #[serde(with = "bigint_json")]
+    pub quality_adj_power: StoragePower,
+}
+
+/// The power of a miner together with the total network power.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MinerPower {
+    pub miner_power: Claim,
+    pub total_power: Claim,
+}
@@ -1,6 +1,9 @@
 pub mod constants;
 pub mod address;
 pub mod utils;
+pub mod cbor;
+pub mod exit_code;
+pub mod token;
 pub mod crypto;
 pub mod bytes;
 pub mod message;
@@ -20,6 +23,8 @@ pub mod miner;
 pub mod sector;
 pub mod deal;
 pub mod piece;
+pub mod actor;
+pub mod state;
 
 pub use num_bigint::BigInt;
 pub use cid::Cid;
@@ -29,17 +34,23 @@ pub use version::Version;
 pub use common::{Permission, Connectedness, PeerAddrInfo};
 pub use libp2p_core::{PeerId};
 pub use utils::{peerid_json::{PeerIdWrapper, PeerIdRefWrapper}, cid_json::{CidJson, CidJsonRef}, bigint_json::BigIntWrapper};
-pub use tipset::{TipSet, TipSetKey};
+pub use tipset::{TipSet, TipSetKey, TipSetError};
 pub use crypto::DomainSeparationTag;
-pub use header::{ChainEpoch, BlockHeader, HeadChange};
+pub use header::{ChainEpoch, BlockHeader, HeadChange, HeadChangeType};
 pub use hash::{H256, Randomness};
-pub use message::{BlockMessages, MessageReceipt, ParentMessage, UnsignedMessage, SignedMessage, ObjStat};
+pub use message::{BlockMessages, MessageReceipt, ParentMessage, UnsignedMessage, SignedMessage, ObjStat,
+                  MessageSendSpec};
 pub use address::Address;
 pub use crypto::{SignatureType, Signature};
 pub use keystore::KeyInfo;
 pub use sync::{SyncState, SyncStateStage, ActiveSync};
 pub use block::BlockMsg;
 pub use mpool::{MpoolChange, MpoolUpdate};
-pub use miner::{MiningBaseInfo, BlockTemplate};
-pub use sector::{SectorSize};
-pub use deal::{Import, DealInfo, StartDealParams, QueryOffer, RetrievalOrder, CommPRet, FileRef};
\ No newline at end of file
+pub use miner::{MiningBaseInfo, BlockTemplate, MinerInfo, MinerPower, Claim};
+pub use sector::{SectorSize, SectorNumber, ChainSectorInfo, SectorOnChainInfo, SectorPreCommitInfo};
+pub use deal::{Import, DealInfo, StartDealParams, QueryOffer, RetrievalOrder, CommPRet, FileRef, MarketBalance,
+               MarketDeal, DealProposal, DealState};
+pub use actor::{Actor, ExecReturn, CreateMinerReturn};
+pub use exit_code::ExitCode;
+pub use token::TokenAmount;
+pub use state::{MsgLookup, InvocResult};
\ No newline at end of file
@@ -2,4 +2,8 @@ use serde::{Serialize, Deserialize};
 
 /// Unpadded size of a piece, in bytes
 #[derive(Clone, Copy, Debug, Default, Ord, PartialOrd, Eq, PartialEq, Hash, Serialize, Deserialize)]
-pub struct UnpaddedPieceSize(pub(crate) u64);
\ No newline at end of file
+pub struct UnpaddedPieceSize(pub(crate) u64);
+
+/// Padded size of a piece, in bytes
+#[derive(Clone, Copy, Debug, Default, Ord, PartialOrd, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct PaddedPieceSize(pub(crate) u64);
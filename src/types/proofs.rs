@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
+use super::cbor::{self, CborEncode};
 use super::utils::bytes_json;
 
 #[repr(u64)]
@@ -29,6 +30,9 @@ pub enum RegisteredProof {
 /// The PoSt election proof of space/time
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash, Serialize, Deserialize)]
 pub struct ElectionProof {
+    /// The number of wins of the election, since Lotus v0.5, `None` before.
+    #[serde(rename = "WinCount", default, skip_serializing_if = "Option::is_none")]
+    pub win_count: Option<i64>,
     /// VRF proof
     #[serde(rename = "VRFProof")]
     #[serde(with = "bytes_json")]
@@ -42,4 +46,25 @@ pub struct PoStProof {
     pub registered_proof: RegisteredProof,
     #[serde(with = "bytes_json")]
     pub proof_bytes: Vec<u8>,
-}
\ No newline at end of file
+}
+// The proofs of nodes older than Lotus v0.5 have no win count.
+impl CborEncode for ElectionProof {
+    fn encode_cbor(&self, buf: &mut Vec<u8>) {
+        match self.win_count {
+            Some(win_count) => {
+                cbor::write_array_header(buf, 2);
+                cbor::write_i64(buf, win_count);
+            }
+            None => cbor::write_array_header(buf, 1),
+        }
+        cbor::write_bytes(buf, &self.vrf_proof);
+    }
+}
+
+impl CborEncode for PoStProof {
+    fn encode_cbor(&self, buf: &mut Vec<u8>) {
+        cbor::write_array_header(buf, 2);
+        cbor::write_i64(buf, self.registered_proof as i64);
+        cbor::write_bytes(buf, &self.proof_bytes);
+    }
+}
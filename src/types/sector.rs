@@ -1,8 +1,10 @@
 use serde::{Serialize, Deserialize};
 use num_bigint::BigInt;
 use cid::Cid;
-use super::utils::cid_json;
+use super::utils::{bigint_json, cid_json};
 use super::proofs::RegisteredProof;
+use super::header::ChainEpoch;
+use super::deal::DealId;
 
 /// SectorNumber is a numeric identifier for a sector. It is usually relative to a miner.
 pub type SectorNumber = u64;
@@ -29,4 +31,44 @@ pub struct SectorInfo {
     #[serde(rename = "SealedCID")]
     #[serde(with = "cid_json")]
     pub sealed_cid: Cid,
-}
\ No newline at end of file
+}
+
+/// Information provided by a miner when pre-committing a sector.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SectorPreCommitInfo {
+    pub registered_proof: RegisteredProof,
+    pub sector_number: SectorNumber,
+    #[serde(rename = "SealedCID")]
+    #[serde(with = "cid_json")]
+    pub sealed_cid: Cid,
+    pub seal_rand_epoch: ChainEpoch,
+    #[serde(rename = "DealIDs")]
+    pub deal_ids: Option<Vec<DealId>>,
+    /// Sector expiration.
+    pub expiration: ChainEpoch,
+}
+
+/// Information stored on-chain for a proven sector.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SectorOnChainInfo {
+    pub info: SectorPreCommitInfo,
+    /// Epoch at which SectorProveCommit is accepted.
+    pub activation_epoch: ChainEpoch,
+    /// Integral of active deals over sector lifetime.
+    #[serde(with = "bigint_json")]
+    pub deal_weight: BigInt,
+    /// Integral of active verified deals over sector lifetime.
+    #[serde(with = "bigint_json")]
+    pub verified_deal_weight: BigInt,
+}
+
+/// A sector of a miner as returned by `StateMinerSectors`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ChainSectorInfo {
+    #[serde(rename = "Info")]
+    pub info: SectorOnChainInfo,
+    #[serde(rename = "ID")]
+    pub id: SectorNumber,
+}
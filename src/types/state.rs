@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+use super::header::ChainEpoch;
+use super::message::{MessageReceipt, UnsignedMessage};
+use super::tipset::TipSetKey;
+
+/// The result of looking up an executed message.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MsgLookup {
+    /// The receipt of applying the message.
+    pub receipt: MessageReceipt,
+    /// The key of the tipset where the message was executed.
+    #[serde(rename = "TipSet")]
+    pub tipset: TipSetKey,
+    /// The height of the tipset.
+    pub height: ChainEpoch,
+}
+
+/// The result of invoking a message without putting it on chain.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct InvocResult {
+    /// The invoked message.
+    pub msg: UnsignedMessage,
+    /// The receipt, none if the message couldn't be applied.
+    pub msg_rct: Option<MessageReceipt>,
+    /// The error of applying the message, empty if none.
+    pub error: String,
+    /// The execution time in nanoseconds.
+    pub duration: u64,
+}
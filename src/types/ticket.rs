@@ -1,4 +1,6 @@
 use serde::{Deserialize, Serialize};
+use super::address::blake2b_variable;
+use super::cbor::{self, CborEncode};
 use super::utils::bytes_json;
 
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash, Serialize, Deserialize)]
@@ -9,6 +11,20 @@ pub struct Ticket {
     pub vrf_proof: Vec<u8>,
 }
 
+impl Ticket {
+    /// The blake2b-256 digest of the VRF proof, which orders the tickets.
+    pub fn digest(&self) -> Vec<u8> {
+        blake2b_variable(&self.vrf_proof, 32)
+    }
+}
+
+impl CborEncode for Ticket {
+    fn encode_cbor(&self, buf: &mut Vec<u8>) {
+        cbor::write_array_header(buf, 1);
+        cbor::write_bytes(buf, &self.vrf_proof);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
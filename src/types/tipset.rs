@@ -1,7 +1,31 @@
+use std::convert::TryFrom;
+use std::fmt;
+use num_bigint::BigInt;
 use serde::{ser, de, Deserialize, Serialize};
+use thiserror::Error;
+use super::address::Address;
+use super::cbor::{self, CborDecode, CborEncode, CborError, Decoder};
 use super::utils::vec_cid_json;
 use cid::Cid;
 use super::header::{BlockHeader, ChainEpoch};
+use super::ticket::Ticket;
+
+/// Errors of building a tipset out of blocks.
+#[derive(Debug, Eq, PartialEq, Error)]
+pub enum TipSetError {
+    #[error("a tipset needs at least one block")]
+    NoBlocks,
+    #[error("block {0} has a different height")]
+    MismatchingHeight(Cid),
+    #[error("block {0} has different parents")]
+    MismatchingParents(Cid),
+    #[error("block {0} has a different parent state")]
+    MismatchingParentState(Cid),
+    #[error("block {0} is given twice")]
+    DuplicateBlock(Cid),
+    #[error("the CIDs or the height don't match the blocks")]
+    MismatchingCids,
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -12,11 +36,198 @@ pub struct TipSet {
     pub height: ChainEpoch,
 }
 
-#[derive(Clone, Debug)]
+impl TipSet {
+    /// Build the tipset of `blocks`, sorted by ticket like Lotus, then by CID.
+    ///
+    /// The blocks must be distinct, and share their height, their parents and the parent state.
+    pub fn new(blocks: Vec<BlockHeader>) -> Result<Self, TipSetError> {
+        let mut blocks = blocks.into_iter().map(|block| (block.cid(), block)).collect::<Vec<_>>();
+        blocks.sort_by_cached_key(|(cid, block)| (block.ticket.digest(), cid.to_bytes()));
+
+        let first = &blocks.first().ok_or(TipSetError::NoBlocks)?.1;
+        // The same blocks are next to each other once sorted.
+        if let Some(pair) = blocks.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(TipSetError::DuplicateBlock(pair[0].0.clone()));
+        }
+        for (cid, block) in &blocks[1..] {
+            if block.height != first.height {
+                return Err(TipSetError::MismatchingHeight(cid.clone()));
+            }
+            if block.parents != first.parents {
+                return Err(TipSetError::MismatchingParents(cid.clone()));
+            }
+            if block.parent_weight != first.parent_weight
+                || block.parent_state_root != first.parent_state_root
+                || block.parent_message_receipts != first.parent_message_receipts
+            {
+                return Err(TipSetError::MismatchingParentState(cid.clone()));
+            }
+        }
+        let height = first.height;
+        let (cids, blocks) = blocks.into_iter().unzip();
+        Ok(TipSet { cids, blocks, height })
+    }
+
+    /// Check that the tipset is the one `new` builds out of its blocks, e.g. for a tipset
+    /// from an untrusted node.
+    pub fn validate(&self) -> Result<(), TipSetError> {
+        let tipset = TipSet::new(self.blocks.clone())?;
+        if tipset.cids != self.cids || tipset.height != self.height {
+            return Err(TipSetError::MismatchingCids);
+        }
+        Ok(())
+    }
+
+    /// The key of the tipset, made of the CIDs of its blocks.
+    pub fn key(&self) -> TipSetKey {
+        TipSetKey::new(self.cids.clone())
+    }
+
+    /// The key of the parent tipset, shared by the blocks, empty if there are no blocks.
+    pub fn parents(&self) -> TipSetKey {
+        TipSetKey::new(self.blocks.first().map(|block| block.parents.clone()).unwrap_or_default())
+    }
+
+    /// The weight of the parent tipset, `None` if there are no blocks.
+    pub fn parent_weight(&self) -> Option<&BigInt> {
+        self.blocks.first().map(|block| &block.parent_weight)
+    }
+
+    /// The state root after the execution of the parent tipset, `None` if there are no blocks.
+    pub fn parent_state_root(&self) -> Option<&Cid> {
+        self.blocks.first().map(|block| &block.parent_state_root)
+    }
+
+    /// The smallest ticket of the blocks, which seeds the randomness of the tipset.
+    pub fn min_ticket(&self) -> Option<&Ticket> {
+        self.blocks.iter().map(|block| &block.ticket).min_by_key(|ticket| ticket.digest())
+    }
+
+    /// The earliest timestamp of the blocks, `None` if there are no blocks.
+    pub fn min_timestamp(&self) -> Option<u64> {
+        self.blocks.iter().map(|block| block.timestamp).min()
+    }
+
+    /// The miners of the blocks, in the order of the blocks.
+    pub fn miners(&self) -> Vec<&Address> {
+        self.blocks.iter().map(|block| &block.miner).collect()
+    }
+}
+
+/// The CIDs of the blocks of a tipset, identifying it.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub struct TipSetKey {
     cids: Vec<Cid>,
 }
 
+impl TipSetKey {
+    /// The key of the blocks `cids`, in the order of the blocks in the tipset.
+    pub fn new(cids: Vec<Cid>) -> Self {
+        TipSetKey { cids }
+    }
+
+    /// The empty key, which selects the head of the chain.
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// The CIDs of the blocks, in order.
+    pub fn cids(&self) -> &[Cid] {
+        &self.cids
+    }
+
+    /// Whether this is the empty key, see `empty`.
+    pub fn is_empty(&self) -> bool {
+        self.cids.is_empty()
+    }
+
+    /// Iterate over the CIDs of the blocks, in order.
+    pub fn iter(&self) -> std::slice::Iter<'_, Cid> {
+        self.cids.iter()
+    }
+
+    /// The canonical encoding of Lotus, the bytes of the CIDs one after the other.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.cids.iter().flat_map(Cid::to_bytes).collect()
+    }
+
+    /// Decode the canonical encoding of `to_bytes`.
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, cid::Error> {
+        let mut cids = Vec::new();
+        while !bytes.is_empty() {
+            let len = cid_len(bytes)?;
+            cids.push(Cid::try_from(&bytes[..len])?);
+            bytes = &bytes[len..];
+        }
+        Ok(TipSetKey::new(cids))
+    }
+}
+
+// The length of the CID at the start of `bytes`.
+fn cid_len(bytes: &[u8]) -> Result<usize, cid::Error> {
+    fn varint(bytes: &[u8]) -> Result<(u64, &[u8]), cid::Error> {
+        unsigned_varint::decode::u64(bytes).map_err(|_| cid::Error::ParsingError)
+    }
+
+    // A CIDv0 is a bare sha2-256 multihash.
+    if bytes.starts_with(&[0x12, 0x20]) {
+        return if bytes.len() >= 34 { Ok(34) } else { Err(cid::Error::InputTooShort) };
+    }
+    let (_version, rest) = varint(bytes)?;
+    let (_codec, rest) = varint(rest)?;
+    let (_hash_code, rest) = varint(rest)?;
+    let (hash_len, rest) = varint(rest)?;
+    if (rest.len() as u64) < hash_len {
+        return Err(cid::Error::InputTooShort);
+    }
+    Ok(bytes.len() - rest.len() + hash_len as usize)
+}
+
+impl From<Vec<Cid>> for TipSetKey {
+    fn from(cids: Vec<Cid>) -> Self {
+        TipSetKey::new(cids)
+    }
+}
+
+impl IntoIterator for TipSetKey {
+    type Item = Cid;
+    type IntoIter = std::vec::IntoIter<Cid>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.cids.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a TipSetKey {
+    type Item = &'a Cid;
+    type IntoIter = std::slice::Iter<'a, Cid>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.cids.iter()
+    }
+}
+
+// Formats like Lotus, `{cid1,cid2}`.
+impl fmt::Display for TipSetKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cids = self.cids.iter().map(Cid::to_string).collect::<Vec<_>>();
+        write!(f, "{{{}}}", cids.join(","))
+    }
+}
+
+// Lotus encodes the key as a byte string of the canonical encoding.
+impl CborEncode for TipSetKey {
+    fn encode_cbor(&self, buf: &mut Vec<u8>) {
+        cbor::write_bytes(buf, &self.to_bytes());
+    }
+}
+
+impl CborDecode for TipSetKey {
+    fn decode_cbor(decoder: &mut Decoder<'_>) -> Result<Self, CborError> {
+        TipSetKey::from_bytes(decoder.read_bytes()?).map_err(|e| CborError::InvalidValue(e.to_string()))
+    }
+}
+
 // Implement JSON serialization for TipsetKey.
 impl ser::Serialize for TipSetKey {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -34,13 +245,20 @@ impl<'de> de::Deserialize<'de> for TipSetKey {
             D: de::Deserializer<'de>,
     {
         let cids = vec_cid_json::deserialize(deserializer)?;
-        Ok(TipSetKey { cids })
+        Ok(TipSetKey::new(cids))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::header::BLOCK;
+
+    // The tipset of `BLOCK` alone.
+    fn tip_set() -> String {
+        let cid = r#"{"/":"bafy2bzacec43cadndvrgpiq3lia65pbyj2t32jltlqp2oszlvknwn5wx3vyii"}"#;
+        format!(r#"{{"Cids":[{}],"Blocks":[{}],"Height":149063}}"#, cid, BLOCK)
+    }
 
     #[derive(Clone, Debug, Serialize, Deserialize)]
     #[serde(rename_all = "PascalCase")]
@@ -58,12 +276,64 @@ mod tests {
 
     #[test]
     fn tip_set_json() {
-        let json = r#"{
-        "Cids":[{"/":"bafy2bzacec43cadndvrgpiq3lia65pbyj2t32jltlqp2oszlvknwn5wx3vyii"}],
-        "Blocks":[{"Miner":"t01000","Ticket":{"VRFProof":"k4aywRis+mYWN56o3OQOAxEFxKSp777TR1h8hcTEeWlLwvERi2oXnTE7xzS0uoLICnEhoGs9BL5MGDYpf3dfmvLD+h7iBimSpl6rY7bysDbuKreKXa9GwAPN3fQqJB1O"},"ElectionProof":{"VRFProof":"g7Ki1qDQtj0Q1o2bRpHZqD++UqFfjPaOJ5WYT2wJjCgGxg/+2L4cozSU/F7IzGIfE1E79C0brMGROGCLMui4qiSZr1D9sJmn+EBwrLjbqpiJEVXqoXoFEkw7/xpFjIat"},"BeaconEntries":null,"WinPoStProof":[{"RegisteredProof":9,"ProofBytes":"scKG734ZjZjlLv1I9z/7R4qmL3M0kpkTKtBa00pGVxA8cd3myhwhocX8BL4pHl8QmMbkPqp5iXh0sbCdJjbJ6/OmAvpATiAYf3R7pTMOdkLvxFofq4NDEtv8t/I4fnOJAcTvG0ozeNA3MM0KjR2X+kfz4Fo4kVflCdhcT9cKlYBO7IiVKYm/RN0zyvJi6pzhmBtryhGzYyNYv3jWVde8qUtIQnD0169SzYVrbZlfF4ydpgGj5PriYRXrCTi9DXmz"}],"Parents":[{"/":"bafy2bzacebwut2il7udv5d3yzscpwbomvj5ocq6lkxh4kcusiy5juesvpun4c"}],"ParentWeight":"629642112","Height":149063,"ParentStateRoot":{"/":"bafy2bzaceae7pqh2wupmp3fqnlbsxx2czjku5rbisl3qdtaa5mehs2hkjak3a"},"ParentMessageReceipts":{"/":"bafy2bzaceaa43et73tgxsoh2xizd4mxhbrcfig4kqp25zfa5scdgkzppllyuu"},"Messages":{"/":"bafy2bzacecgw6dqj4bctnbnyqfujltkwu7xc7ttaaato4i5miroxr4bayhfea"},"BLSAggregate":{"Type":2,"Data":"wAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"},"Timestamp":1592693392,"BlockSig":{"Type":2,"Data":"l+3ZTa9Q1mj8UcVMAetZSuZphQQJUDfaSbXbZf6rNTBhrqE7feLMcTCCMcUOClNnFH+P8HQmOZ8YwH47vU2vw6maLU33bS5Bc6+MvF7gjFx2pRHgq5GM8SPunDA3fKFe"},"ForkSignaling":0}],
-        "Height":149063}"#;
-
-        let tip_set = serde_json::from_str::<TipSet>(json);
+        let tip_set = serde_json::from_str::<TipSet>(&tip_set());
         println!("{:?}", tip_set);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn tip_set_key_bytes() {
+        let v1 = Cid::try_from("bafy2bzacec43cadndvrgpiq3lia65pbyj2t32jltlqp2oszlvknwn5wx3vyii").unwrap();
+        let v0 = Cid::try_from("QmdfTbBqBPQ7VNxZEYEj14VmRuZBkqFbiwReogJgS1zR1n").unwrap();
+        let key = TipSetKey::new(vec![v1.clone(), v0.clone()]);
+        let bytes = key.to_bytes();
+        assert_eq!(bytes, [v1.to_bytes(), v0.to_bytes()].concat());
+        assert_eq!(TipSetKey::from_bytes(&bytes), Ok(key.clone()));
+        assert_eq!(TipSetKey::from_bytes(&bytes[..bytes.len() - 1]), Err(cid::Error::InputTooShort));
+        assert_eq!(TipSetKey::from_cbor(&key.to_cbor()), Ok(key.clone()));
+        assert_eq!(TipSetKey::from_bytes(&[]), Ok(TipSetKey::empty()));
+
+        assert_eq!(key.iter().collect::<Vec<_>>(), vec![&v1, &v0]);
+        assert_eq!(key.to_string(), format!("{{{},{}}}", v1, v0));
+        let mut keys = std::collections::HashSet::new();
+        keys.insert(key.clone());
+        assert!(keys.contains(&TipSetKey::from(vec![v1, v0])));
+    }
+
+    #[test]
+    fn tip_set_from_blocks() {
+        let tip_set = serde_json::from_str::<TipSet>(&tip_set()).unwrap();
+        assert_eq!(tip_set.validate(), Ok(()));
+
+        let first = tip_set.blocks[0].clone();
+        let mut second = first.clone();
+        second.miner = Address::new_id_addr(1001).unwrap();
+        second.ticket = Ticket { vrf_proof: vec![1, 2, 3] };
+        second.timestamp -= 1;
+        // The blake2b-256 digest of the ticket of `second` is the smallest.
+        assert!(second.ticket.digest() < first.ticket.digest());
+        let tip_set = TipSet::new(vec![first.clone(), second.clone()]).unwrap();
+        assert_eq!(tip_set.cids, vec![second.cid(), first.cid()]);
+        assert_eq!(tip_set.height, 149063);
+        assert_eq!(tip_set.miners(), vec![&second.miner, &first.miner]);
+        assert_eq!(tip_set.min_ticket(), Some(&second.ticket));
+        assert_eq!(tip_set.min_timestamp(), Some(second.timestamp));
+        assert_eq!(tip_set.parent_weight(), Some(&BigInt::from(629642112u64)));
+        assert_eq!(tip_set.parent_state_root(), Some(&first.parent_state_root));
+        assert_eq!(tip_set.parents(), TipSetKey::new(first.parents.clone()));
+
+        let mut unordered = tip_set.clone();
+        unordered.cids.reverse();
+        assert_eq!(unordered.validate(), Err(TipSetError::MismatchingCids));
+
+        assert_eq!(TipSet::new(vec![]).unwrap_err(), TipSetError::NoBlocks);
+        let duplicate = TipSet::new(vec![first.clone(), second.clone(), first.clone()]);
+        assert_eq!(duplicate.unwrap_err(), TipSetError::DuplicateBlock(first.cid()));
+        let mut other = second.clone();
+        other.height += 1;
+        // `other` comes first, by its ticket.
+        assert_eq!(TipSet::new(vec![first.clone(), other]).unwrap_err(), TipSetError::MismatchingHeight(first.cid()));
+        let mut other = second;
+        other.parent_weight += 1;
+        assert!(matches!(TipSet::new(vec![first, other]), Err(TipSetError::MismatchingParentState(_))));
+    }
+}
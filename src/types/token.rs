@@ -0,0 +1,403 @@
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+use num_bigint::{BigInt, Sign};
+use num_traits::{Signed, Zero};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use super::cbor::{CborDecode, CborEncode, CborError, Decoder};
+use super::utils::bigint_json;
+
+/// The denominations of FIL.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Unit {
+    Fil,
+    MilliFil,
+    MicroFil,
+    NanoFil,
+    PicoFil,
+    FemtoFil,
+    AttoFil,
+}
+
+impl Unit {
+    const ALL: [Unit; 7] = [
+        Unit::Fil,
+        Unit::MilliFil,
+        Unit::MicroFil,
+        Unit::NanoFil,
+        Unit::PicoFil,
+        Unit::FemtoFil,
+        Unit::AttoFil,
+    ];
+
+    /// The number of decimals of the unit in attoFIL, e.g. 9 for nanoFIL.
+    pub fn decimals(self) -> usize {
+        match self {
+            Unit::Fil => 18,
+            Unit::MilliFil => 15,
+            Unit::MicroFil => 12,
+            Unit::NanoFil => 9,
+            Unit::PicoFil => 6,
+            Unit::FemtoFil => 3,
+            Unit::AttoFil => 0,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Unit::Fil => "FIL",
+            Unit::MilliFil => "milliFIL",
+            Unit::MicroFil => "microFIL",
+            Unit::NanoFil => "nanoFIL",
+            Unit::PicoFil => "picoFIL",
+            Unit::FemtoFil => "femtoFIL",
+            Unit::AttoFil => "attoFIL",
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Unit {
+    type Err = TokenAmountError;
+
+    /// Parse the name of a unit, case-insensitively.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Unit::ALL
+            .iter()
+            .copied()
+            .find(|unit| unit.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| TokenAmountError::UnknownUnit(s.to_string()))
+    }
+}
+
+/// Errors of parsing a token amount.
+#[derive(Debug, Eq, PartialEq, Error)]
+pub enum TokenAmountError {
+    #[error("invalid token amount: {0:?}")]
+    Invalid(String),
+    #[error("unknown unit: {0}")]
+    UnknownUnit(String),
+    #[error("{0:?} is more precise than attoFIL")]
+    TooPrecise(String),
+}
+
+/// An amount of FIL, stored in attoFIL (10^-18 FIL).
+///
+/// It's serialized as the decimal string of attoFIL like the `BigInt` of Lotus, and parsed
+/// from strings such as `1.5 FIL`, `300 nanoFIL` or `12 attoFIL`. A bare number is in FIL.
+#[derive(Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TokenAmount(#[serde(with = "bigint_json")] BigInt);
+
+impl TokenAmount {
+    pub fn zero() -> Self {
+        Self::default()
+    }
+
+    pub fn from_atto<I: Into<BigInt>>(atto: I) -> Self {
+        Self(atto.into())
+    }
+
+    pub fn from_nano<I: Into<BigInt>>(nano: I) -> Self {
+        Self::from_unit(nano, Unit::NanoFil)
+    }
+
+    pub fn from_fil<I: Into<BigInt>>(fil: I) -> Self {
+        Self::from_unit(fil, Unit::Fil)
+    }
+
+    pub fn from_unit<I: Into<BigInt>>(amount: I, unit: Unit) -> Self {
+        Self(amount.into() * pow10(unit.decimals()))
+    }
+
+    pub fn atto(&self) -> &BigInt {
+        &self.0
+    }
+
+    pub fn into_atto(self) -> BigInt {
+        self.0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0.is_negative()
+    }
+
+    /// `self + other`, or `None` if the sum is negative.
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        non_negative(&self.0 + &other.0)
+    }
+
+    /// `self - other`, or `None` if `other` is larger than `self`.
+    pub fn checked_sub(&self, other: &Self) -> Option<Self> {
+        non_negative(&self.0 - &other.0)
+    }
+
+    /// `self * n`, or `None` if the product is negative.
+    pub fn checked_mul(&self, n: i64) -> Option<Self> {
+        non_negative(&self.0 * n)
+    }
+
+    /// `self / n` rounded toward zero, or `None` if `n` is zero or the quotient is negative.
+    pub fn checked_div(&self, n: i64) -> Option<Self> {
+        if n == 0 {
+            return None;
+        }
+        non_negative(&self.0 / n)
+    }
+
+    /// Display the amount in `unit`. Like the `Display` of the amount, the precision
+    /// (e.g. `{:.3}`) rounds to the number of decimals, else no trailing zeros are shown.
+    pub fn display_in(&self, unit: Unit) -> impl fmt::Display + '_ {
+        DisplayIn { amount: self, unit }
+    }
+}
+
+fn pow10(exp: usize) -> BigInt {
+    num_traits::pow(BigInt::from(10), exp)
+}
+
+fn non_negative(atto: BigInt) -> Option<TokenAmount> {
+    if atto.is_negative() {
+        None
+    } else {
+        Some(TokenAmount(atto))
+    }
+}
+
+struct DisplayIn<'a> {
+    amount: &'a TokenAmount,
+    unit: Unit,
+}
+
+impl fmt::Display for DisplayIn<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let decimals = self.unit.decimals();
+        let mut atto = self.amount.0.abs();
+        if let Some(precision) = f.precision().filter(|precision| *precision < decimals) {
+            // Round half away from zero.
+            let step = pow10(decimals - precision);
+            atto = (atto + &step / 2) / &step * step;
+        }
+
+        let digits = format!("{:0>width$}", atto.to_string(), width = decimals + 1);
+        let (int, frac) = digits.split_at(digits.len() - decimals);
+        let frac = match f.precision() {
+            Some(precision) if precision <= decimals => frac[..precision].to_string(),
+            Some(precision) => format!("{:0<width$}", frac, width = precision),
+            None => frac.trim_end_matches('0').to_string(),
+        };
+
+        if self.amount.is_negative() && !atto.is_zero() {
+            f.write_str("-")?;
+        }
+        if frac.is_empty() {
+            write!(f, "{} {}", int, self.unit)
+        } else {
+            write!(f, "{}.{} {}", int, frac, self.unit)
+        }
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.display_in(Unit::Fil), f)
+    }
+}
+
+impl fmt::Debug for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TokenAmount({} attoFIL)", self.0)
+    }
+}
+
+impl FromStr for TokenAmount {
+    type Err = TokenAmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || TokenAmountError::Invalid(s.to_string());
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let unit = match unit.trim() {
+            "" => Unit::Fil,
+            unit => unit.parse()?,
+        };
+
+        let (sign, number) = match number.strip_prefix('-') {
+            Some(number) => (Sign::Minus, number),
+            None => (Sign::Plus, number.strip_prefix('+').unwrap_or(number)),
+        };
+        let (int, frac) = match number.find('.') {
+            Some(dot) => (&number[..dot], &number[dot + 1..]),
+            None => (number, ""),
+        };
+        let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if (int.is_empty() && frac.is_empty()) || !is_digits(int) || !is_digits(frac) {
+            return Err(invalid());
+        }
+        let frac = frac.trim_end_matches('0');
+        if frac.len() > unit.decimals() {
+            return Err(TokenAmountError::TooPrecise(s.to_string()));
+        }
+
+        let digits = format!("{}{:0<width$}", int, frac, width = unit.decimals());
+        let magnitude = BigInt::parse_bytes(digits.as_bytes(), 10).unwrap_or_default();
+        Ok(Self(match sign {
+            Sign::Minus => -magnitude,
+            _ => magnitude,
+        }))
+    }
+}
+
+impl From<BigInt> for TokenAmount {
+    fn from(atto: BigInt) -> Self {
+        Self(atto)
+    }
+}
+
+impl From<TokenAmount> for BigInt {
+    fn from(amount: TokenAmount) -> Self {
+        amount.0
+    }
+}
+
+impl Add for TokenAmount {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self(self.0 + other.0)
+    }
+}
+
+impl<'a> Add<&'a TokenAmount> for &'a TokenAmount {
+    type Output = TokenAmount;
+
+    fn add(self, other: Self) -> TokenAmount {
+        TokenAmount(&self.0 + &other.0)
+    }
+}
+
+impl AddAssign for TokenAmount {
+    fn add_assign(&mut self, other: Self) {
+        self.0 += other.0;
+    }
+}
+
+impl Sub for TokenAmount {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self(self.0 - other.0)
+    }
+}
+
+impl<'a> Sub<&'a TokenAmount> for &'a TokenAmount {
+    type Output = TokenAmount;
+
+    fn sub(self, other: Self) -> TokenAmount {
+        TokenAmount(&self.0 - &other.0)
+    }
+}
+
+impl SubAssign for TokenAmount {
+    fn sub_assign(&mut self, other: Self) {
+        self.0 -= other.0;
+    }
+}
+
+impl Neg for TokenAmount {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+impl Sum for TokenAmount {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), Add::add)
+    }
+}
+
+impl<'a> Sum<&'a TokenAmount> for TokenAmount {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), |sum, amount| sum + amount.clone())
+    }
+}
+
+impl CborEncode for TokenAmount {
+    fn encode_cbor(&self, buf: &mut Vec<u8>) {
+        self.0.encode_cbor(buf);
+    }
+}
+
+impl CborDecode for TokenAmount {
+    fn decode_cbor(decoder: &mut Decoder<'_>) -> Result<Self, CborError> {
+        BigInt::decode_cbor(decoder).map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_amount_parse() {
+        let parse = |s: &str| s.parse::<TokenAmount>();
+        assert_eq!(parse("1.5 FIL"), Ok(TokenAmount::from_nano(1_500_000_000)));
+        assert_eq!(parse("1.5"), Ok(TokenAmount::from_nano(1_500_000_000)));
+        assert_eq!(parse("300 nanoFIL"), Ok(TokenAmount::from_atto(300_000_000_000u64)));
+        assert_eq!(parse("12attofil"), Ok(TokenAmount::from_atto(12)));
+        assert_eq!(parse("-0.000000000000000001 FIL"), Ok(TokenAmount::from_atto(-1)));
+        assert_eq!(parse(".5 milliFIL"), Ok(TokenAmount::from_unit(500, Unit::MicroFil)));
+        assert_eq!(parse("2.50 attoFIL"), Err(TokenAmountError::TooPrecise("2.50 attoFIL".into())));
+        assert_eq!(parse("1 FILE"), Err(TokenAmountError::UnknownUnit("FILE".into())));
+        assert_eq!(parse("1.2.3 FIL"), Err(TokenAmountError::Invalid("1.2.3 FIL".into())));
+        assert_eq!(parse(" FIL"), Err(TokenAmountError::Invalid(" FIL".into())));
+    }
+
+    #[test]
+    fn token_amount_format() {
+        let amount = TokenAmount::from_atto(1_234_567_890_123_456_789u64);
+        assert_eq!(amount.to_string(), "1.234567890123456789 FIL");
+        assert_eq!(format!("{:.3}", amount), "1.235 FIL");
+        assert_eq!(format!("{:.0}", amount), "1 FIL");
+        assert_eq!(format!("{:.2}", amount.display_in(Unit::NanoFil)), "1234567890.12 nanoFIL");
+        assert_eq!(TokenAmount::from_fil(2).to_string(), "2 FIL");
+        assert_eq!(format!("{:.2}", TokenAmount::from_fil(2)), "2.00 FIL");
+        assert_eq!(TokenAmount::from_atto(-5).display_in(Unit::AttoFil).to_string(), "-5 attoFIL");
+        assert_eq!(format!("{:.1}", TokenAmount::from_atto(-5)), "0.0 FIL");
+        assert_eq!(amount.to_string().parse::<TokenAmount>(), Ok(amount));
+    }
+
+    #[test]
+    fn token_amount_arithmetic() {
+        let one = TokenAmount::from_fil(1);
+        let half = TokenAmount::from_nano(500_000_000);
+        assert_eq!(one.checked_sub(&half), Some(half.clone()));
+        assert_eq!(half.checked_sub(&one), None);
+        assert_eq!(half.checked_add(&half), Some(one.clone()));
+        assert_eq!(half.checked_mul(2), Some(one.clone()));
+        assert_eq!(one.checked_div(2), Some(half.clone()));
+        assert_eq!(one.checked_div(0), None);
+        assert_eq!(vec![half.clone(), half.clone()].iter().sum::<TokenAmount>(), one);
+        assert_eq!(&one - &half, half);
+
+        assert_eq!(serde_json::to_string(&half).unwrap(), r#""500000000000000000""#);
+        assert_eq!(serde_json::from_str::<TokenAmount>(r#""500000000000000000""#).unwrap(), half);
+        assert_eq!(TokenAmount::from_cbor(&half.to_cbor()).unwrap(), half);
+    }
+}
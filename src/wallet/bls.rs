@@ -0,0 +1,131 @@
+//! BLS12-381 keys as used by Lotus: public keys in G1 (48 bytes), signatures
+//! in G2 (96 bytes), and the `BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_` ciphersuite.
+use std::collections::HashSet;
+use std::convert::TryInto;
+use bls12_381::{G1Affine, G2Affine, G2Projective, G2Prepared, Gt, Scalar, multi_miller_loop};
+use bls12_381::hash_to_curve::{HashToCurve, ExpandMsgXmd};
+use rand::RngCore;
+use crate::types::constants::{BLS_PRIVATE_KEY_LEN, BLS_PUBLIC_KEY_LEN, BLS_SIGNATURE_LEN};
+use crate::types::crypto::CryptoError;
+
+const CIPHERSUITE: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_";
+
+/// Generate a new private key (little-endian scalar).
+pub fn generate_private_key() -> Vec<u8> {
+    let mut seed = [0u8; 64];
+    rand::rngs::OsRng.fill_bytes(&mut seed);
+    Scalar::from_bytes_wide(&seed).to_bytes().to_vec()
+}
+
+/// Return the compressed public key (48 bytes) of the private key.
+pub fn public_key(private_key: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let secret = parse_private_key(private_key)?;
+    Ok(G1Affine::from(G1Affine::generator() * secret).to_compressed().to_vec())
+}
+
+/// Sign the data with the private key.
+pub fn sign(private_key: &[u8], data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let secret = parse_private_key(private_key)?;
+    Ok(G2Affine::from(hash(data) * secret).to_compressed().to_vec())
+}
+
+/// Verify the signature of the data against the public key.
+pub fn verify(signature: &[u8], public_key: &[u8], data: &[u8]) -> Result<(), CryptoError> {
+    verify_aggregate(signature, &[public_key], &[data])
+}
+
+/// Aggregate the signatures into one.
+pub fn aggregate(signatures: &[&[u8]]) -> Result<Vec<u8>, CryptoError> {
+    let mut sum = G2Projective::identity();
+    for signature in signatures {
+        sum += parse_signature(signature)?;
+    }
+    Ok(G2Affine::from(sum).to_compressed().to_vec())
+}
+
+/// Verify the aggregated signature, where `data[i]` was signed by the key `public_keys[i]`.
+///
+/// Like Lotus, the signed data must be distinct.
+pub fn verify_aggregate(signature: &[u8], public_keys: &[&[u8]], data: &[&[u8]]) -> Result<(), CryptoError> {
+    if public_keys.len() != data.len() {
+        return Err(CryptoError::Bls(format!(
+            "{} public keys for {} messages",
+            public_keys.len(),
+            data.len()
+        )));
+    }
+    if data.iter().collect::<HashSet<_>>().len() != data.len() {
+        return Err(CryptoError::VerifyFailed);
+    }
+    let signature = parse_signature(signature)?;
+
+    // e(g1, signature) == e(pk_1, H(m_1)) * ... * e(pk_n, H(m_n))
+    let mut terms = Vec::with_capacity(1 + data.len());
+    terms.push((-G1Affine::generator(), G2Prepared::from(signature)));
+    for (public_key, data) in public_keys.iter().zip(data) {
+        terms.push((parse_public_key(public_key)?, G2Prepared::from(hash(data))));
+    }
+    let terms = terms.iter().map(|(p, q)| (p, q)).collect::<Vec<_>>();
+
+    if multi_miller_loop(&terms).final_exponentiation() == Gt::identity() {
+        Ok(())
+    } else {
+        Err(CryptoError::VerifyFailed)
+    }
+}
+
+fn hash(data: &[u8]) -> G2Affine {
+    G2Affine::from(<G2Projective as HashToCurve<ExpandMsgXmd<sha2::Sha256>>>::hash_to_curve(data, CIPHERSUITE))
+}
+
+fn parse_private_key(bytes: &[u8]) -> Result<Scalar, CryptoError> {
+    let bytes: [u8; BLS_PRIVATE_KEY_LEN] = bytes
+        .try_into()
+        .map_err(|_| CryptoError::Bls("invalid private key length".into()))?;
+    Option::from(Scalar::from_bytes(&bytes)).ok_or_else(|| CryptoError::Bls("invalid private key".into()))
+}
+
+fn parse_public_key(bytes: &[u8]) -> Result<G1Affine, CryptoError> {
+    let bytes: [u8; BLS_PUBLIC_KEY_LEN] = bytes
+        .try_into()
+        .map_err(|_| CryptoError::Bls("invalid public key length".into()))?;
+    Option::from(G1Affine::from_compressed(&bytes)).ok_or_else(|| CryptoError::Bls("invalid public key".into()))
+}
+
+fn parse_signature(bytes: &[u8]) -> Result<G2Affine, CryptoError> {
+    let bytes: [u8; BLS_SIGNATURE_LEN] = bytes
+        .try_into()
+        .map_err(|_| CryptoError::Bls("invalid signature length".into()))?;
+    Option::from(G2Affine::from_compressed(&bytes)).ok_or_else(|| CryptoError::Bls("invalid signature".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_and_aggregate() {
+        let keys = (0..3).map(|_| generate_private_key()).collect::<Vec<_>>();
+        let public_keys = keys.iter().map(|key| public_key(key).unwrap()).collect::<Vec<_>>();
+        let public_keys = public_keys.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let data: Vec<&[u8]> = vec![b"a", b"b", b"c"];
+
+        let signatures = keys
+            .iter()
+            .zip(&data)
+            .map(|(key, data)| sign(key, data).unwrap())
+            .collect::<Vec<_>>();
+        assert!(verify(&signatures[0], public_keys[0], data[0]).is_ok());
+        assert_eq!(verify(&signatures[0], public_keys[1], data[0]), Err(CryptoError::VerifyFailed));
+
+        let signatures = signatures.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let aggregated = aggregate(&signatures).unwrap();
+        assert!(verify_aggregate(&aggregated, &public_keys, &data).is_ok());
+        assert!(verify_aggregate(&aggregated, &public_keys[..2], &data[..2]).is_err());
+
+        // no messages, the aggregate is the identity point
+        let empty = aggregate(&[]).unwrap();
+        assert_eq!(empty[0], 0xc0);
+        assert!(verify_aggregate(&empty, &[], &[]).is_ok());
+    }
+}
@@ -0,0 +1,159 @@
+//! Offline wallet, which keeps the private keys in process and signs messages
+//! without sending them to a node.
+pub mod secp256k1;
+pub mod bls;
+
+use std::collections::HashMap;
+use std::fmt;
+use crate::api::MpoolApi;
+use crate::error::{Error, Result};
+use crate::types::{Address, Cid, KeyInfo, Signature, SignedMessage, UnsignedMessage};
+use crate::types::keystore::KeyType;
+
+/// A wallet holding private keys locally.
+#[derive(Clone, Default)]
+pub struct LocalWallet {
+    keys: HashMap<Address, KeyInfo>,
+}
+
+impl LocalWallet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Generate a new key of the given type, returning its address.
+    pub fn generate(&mut self, r#type: KeyType) -> Result<Address> {
+        let private_key = match r#type {
+            KeyType::Secp256k1 => secp256k1::generate_private_key(),
+            KeyType::Bls => bls::generate_private_key(),
+            ty => return Err(Error::Signing(format!("unsupported key type: {}", ty))),
+        };
+        self.import(KeyInfo {
+            r#type,
+            private_key: private_key.into(),
+        })
+    }
+
+    /// Import a key, returning its address.
+    pub fn import(&mut self, info: KeyInfo) -> Result<Address> {
+        let addr = key_address(&info)?;
+        self.keys.insert(addr.clone(), info);
+        Ok(addr)
+    }
+
+    /// Export the key of the address.
+    pub fn export(&self, addr: &Address) -> Result<KeyInfo> {
+        self.key(addr).cloned()
+    }
+
+    /// Remove the key of the address.
+    pub fn remove(&mut self, addr: &Address) -> Option<KeyInfo> {
+        self.keys.remove(addr)
+    }
+
+    pub fn has(&self, addr: &Address) -> bool {
+        self.keys.contains_key(addr)
+    }
+
+    pub fn list(&self) -> Vec<Address> {
+        self.keys.keys().cloned().collect()
+    }
+
+    /// Sign the data with the key of the address.
+    pub fn sign(&self, addr: &Address, data: &[u8]) -> Result<Signature> {
+        let info = self.key(addr)?;
+        match info.r#type {
+            KeyType::Secp256k1 => Ok(Signature::new_secp(secp256k1::sign(info.private_key.as_inner(), data)?)),
+            KeyType::Bls => Ok(Signature::new_bls(bls::sign(info.private_key.as_inner(), data)?)),
+            ref ty => Err(Error::Signing(format!("unsupported key type: {}", ty))),
+        }
+    }
+
+    /// Sign the CID of the message with the key of its sender.
+    ///
+    /// The sender must be the key address, not the ID address, of the account.
+    pub fn sign_message(&self, msg: UnsignedMessage) -> Result<SignedMessage> {
+        let signature = self.sign(&msg.from, &msg.cid().to_bytes())?;
+        Ok(SignedMessage {
+            message: msg,
+            signature,
+        })
+    }
+
+    /// Sign the message and push it to the message pool of the node.
+    pub async fn push_message<A>(&self, api: &A, msg: UnsignedMessage) -> Result<Cid>
+        where
+            A: MpoolApi + Sync,
+    {
+        let signed = self.sign_message(msg)?;
+        api.mpool_push(&signed).await
+    }
+
+    fn key(&self, addr: &Address) -> Result<&KeyInfo> {
+        self.keys
+            .get(addr)
+            .ok_or_else(|| Error::Signing(format!("key not found: {}", addr)))
+    }
+}
+
+// Never print the private keys.
+impl fmt::Debug for LocalWallet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalWallet")
+            .field("addresses", &self.keys.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Derive the address of the key.
+pub fn key_address(info: &KeyInfo) -> Result<Address> {
+    let addr = match info.r#type {
+        KeyType::Secp256k1 => {
+            let public_key = secp256k1::public_key(info.private_key.as_inner())?;
+            Address::new_secp256k1_addr(&public_key)
+        }
+        KeyType::Bls => {
+            let public_key = bls::public_key(info.private_key.as_inner())?;
+            Address::new_bls_addr(&public_key)
+        }
+        ref ty => return Err(Error::Signing(format!("unsupported key type: {}", ty))),
+    };
+    addr.map_err(|e| Error::Signing(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::address::Protocol;
+    use crate::types::message::message;
+
+    #[test]
+    fn secp256k1_sign_message() {
+        let mut wallet = LocalWallet::new();
+        let from = wallet.generate(KeyType::Secp256k1).unwrap();
+        assert_eq!(from.protocol(), Protocol::Secp256k1);
+        assert_eq!(key_address(&wallet.export(&from).unwrap()).unwrap(), from);
+
+        let msg = message(from.clone());
+        let signed = wallet.sign_message(msg.clone()).unwrap();
+        assert_eq!(signed.message, msg);
+
+        assert!(signed.verify().is_ok());
+
+        let stranger = Address::new_id_addr(1).unwrap();
+        assert!(wallet.sign(&stranger, b"data").is_err());
+    }
+
+    #[test]
+    fn bls_sign_message() {
+        let mut wallet = LocalWallet::new();
+        let from = wallet.generate(KeyType::Bls).unwrap();
+        assert_eq!(from.protocol(), Protocol::Bls);
+        assert!(from.to_string().starts_with("t3"));
+
+        let msg = message(from.clone());
+        let signed = wallet.sign_message(msg.clone()).unwrap();
+        assert_eq!(signed.cid(), msg.cid());
+        assert!(signed.verify().is_ok());
+    }
+}
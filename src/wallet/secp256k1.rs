@@ -0,0 +1,39 @@
+//! Secp256k1 keys as used by Lotus: signatures are made over the blake2b-256
+//! hash of the data and serialized as `r || s || recovery id` (65 bytes).
+use secp256k1::{Message, PublicKey, RecoveryId, SecretKey, Signature};
+use crate::types::address::blake2b_variable;
+use crate::types::constants::SECP256K1_SIGNATURE_LEN;
+use crate::types::crypto::CryptoError;
+
+/// Generate a new private key.
+pub fn generate_private_key() -> Vec<u8> {
+    SecretKey::random(&mut rand::rngs::OsRng).serialize().to_vec()
+}
+
+/// Return the uncompressed public key (65 bytes) of the private key.
+pub fn public_key(private_key: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let secret = SecretKey::parse_slice(private_key)?;
+    Ok(PublicKey::from_secret_key(&secret).serialize().to_vec())
+}
+
+/// Sign the data with the private key.
+pub fn sign(private_key: &[u8], data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let secret = SecretKey::parse_slice(private_key)?;
+    let message = Message::parse_slice(&blake2b_variable(data, 32))?;
+    let (signature, recovery_id) = secp256k1::sign(&message, &secret);
+
+    let mut bytes = signature.serialize().to_vec();
+    bytes.push(recovery_id.serialize());
+    Ok(bytes)
+}
+
+/// Recover the uncompressed public key (65 bytes) which signed the data.
+pub fn recover_public_key(signature: &[u8], data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if signature.len() != SECP256K1_SIGNATURE_LEN + 1 {
+        return Err(CryptoError::Secp256k1(secp256k1::Error::InvalidSignature));
+    }
+    let message = Message::parse_slice(&blake2b_variable(data, 32))?;
+    let recovery_id = RecoveryId::parse(signature[SECP256K1_SIGNATURE_LEN])?;
+    let signature = Signature::parse_slice(&signature[..SECP256K1_SIGNATURE_LEN])?;
+    Ok(secp256k1::recover(&message, &signature, &recovery_id)?.serialize().to_vec())
+}